@@ -6,7 +6,15 @@
 //! `&[u8]` buffer of binary contents.  Created transducers will have a
 //! [`transducers::Transducer`] trait.
 //!
-//! Only weighted transducers are supported at the moment.
+//! Both weighted (`HFST_OLW`) and unweighted (`HFST_OL`) transducers are
+//! supported, including ones using flag diacritics.  Transducers can be
+//! written back to the binary format with [`transducers::write_transducer()`],
+//! and converted from and to AT&T text format with [`transducers::compile_att()`]
+//! and [`transducers::export_att()`].
+//!
+//! The [`speller`] module checks spelling and suggests corrections with
+//! `hfst-ospell` spellers.  Reading `.zhfst` archives requires the `zhfst`
+//! feature.
 //!
 //! # Examples
 //!
@@ -22,6 +30,17 @@
 //! //     ("лэсьтӥськон+N+Pl+Nom", 0.0),
 //! // ])
 //! ```
+//!
+//! An analyser can be inverted to get a generator:
+//!
+//! ```no_run
+//! use hfstol::Transducer;
+//! # let content = std::fs::read("./analyser-gt-desc.hfstol").unwrap();
+//! # let t = hfstol::read_transducer(&content).unwrap();
+//! let generator = t.invert().unwrap();
+//! println!("{:?}", generator.lookup("лэсьтӥськон+N+Pl+Nom"));
+//! // Ok([("лэсьтӥськонъёс", 0.0)])
+//! ```
 
 #![no_std]
 extern crate alloc;
@@ -31,12 +50,19 @@ extern crate std;
 
 pub mod transducers;
 pub mod trie;
+pub mod speller;
 
-mod mutex;
 mod parser_utils;
 
 pub use transducers::{
+    Analysis,
     Error,
+    LookupOptions,
     Transducer,
     read_transducer,
+    compile_att,
+    export_att,
+    read_transducers,
+    write_transducer,
 };
+pub use speller::Speller;
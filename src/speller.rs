@@ -0,0 +1,291 @@
+//! Spell checking with `hfst-ospell` spellers.
+//!
+//! A speller consists of an acceptor, which recognizes correct words, and an
+//! error model, which maps misspelled strings to candidate corrections.
+//! Suggestions are found by a best-first lookup in the error model composed
+//! with the acceptor (see [`ComposedTransducer`]), so only corrections known
+//! to the acceptor are explored.
+//!
+//! Spellers are distributed as `.zhfst` archives, which can be read with
+//! `Speller::from_zhfst()` when the `zhfst` feature is enabled.
+
+use alloc::{
+    string::String,
+    vec::Vec,
+    vec,
+};
+use core::ops::ControlFlow;
+use crate::transducers::{
+    BoxedTransducer, ComposedTransducer, Error, LookupOptions, Transducer, Weight,
+};
+
+/// Default number of search states a single [`Speller::suggest()`] call may
+/// expand.
+pub const DEFAULT_SEARCH_LIMIT: usize = 100_000;
+
+/// Speller information from the `info` section of `index.xml`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpellerMetadata {
+    pub locale: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub date: Option<String>,
+    pub producer: Option<String>,
+}
+
+impl SpellerMetadata {
+    /// Extracts the metadata from the contents of `index.xml`.
+    ///
+    /// Missing elements are left as [`None`].  Only the first of several
+    /// localized titles or descriptions is kept.
+    pub fn parse(xml: &str) -> SpellerMetadata {
+        let info = element(xml, "info").unwrap_or("");
+        let field = |name| element(info, name).map(unescape);
+        SpellerMetadata {
+            locale: field("locale"),
+            title: field("title"),
+            description: field("description"),
+            version: field("version"),
+            date: field("date"),
+            producer: field("producer"),
+        }
+    }
+}
+
+/// Returns contents of the first element with the given name.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+    loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+        let Some(after) = rest.strip_prefix(name) else {
+            continue;
+        };
+        if !after.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            continue;
+        }
+        let end = after.find('>')?;
+        if after[..end].ends_with('/') {
+            return Some("");
+        }
+        let body = &after[end + 1..];
+        let mut close = String::from("</");
+        close.push_str(name);
+        return body.find(close.as_str()).map(|end| &body[..end]);
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Spell checker built from an acceptor and an error model.
+pub struct Speller {
+    /// The error model composed with the acceptor.
+    composed: ComposedTransducer<BoxedTransducer, BoxedTransducer>,
+    metadata: SpellerMetadata,
+    max_weight: Option<Weight>,
+    search_limit: usize,
+}
+
+impl Speller {
+    /// Creates a speller from an acceptor of correct words and an error
+    /// model.
+    ///
+    /// Both transducers must be backed by transition tables, otherwise
+    /// [`Error::UnsupportedTransducerError`] is returned.
+    pub fn new(acceptor: BoxedTransducer, errmodel: BoxedTransducer) -> Result<Self, Error> {
+        Ok(Speller {
+            composed: ComposedTransducer::new(errmodel, acceptor)?,
+            metadata: SpellerMetadata::default(),
+            max_weight: None,
+            search_limit: DEFAULT_SEARCH_LIMIT,
+        })
+    }
+
+    /// Reads a speller from the contents of a `.zhfst` archive.
+    ///
+    /// The archive must contain `acceptor.default.hfst` and
+    /// `errmodel.default.hfst`.  Metadata is read from `index.xml` if it is
+    /// present.
+    #[cfg(feature = "zhfst")]
+    pub fn from_zhfst(archive: &[u8]) -> Result<Self, Error> {
+        use std::io::Read;
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive))
+            .map_err(Error::ArchiveError)?;
+        let mut read = |name: &str| -> Result<Vec<u8>, zip::result::ZipError> {
+            let mut content = vec![];
+            archive.by_name(name)?.read_to_end(&mut content)?;
+            Ok(content)
+        };
+        let acceptor = read("acceptor.default.hfst").map_err(Error::ArchiveError)?;
+        let errmodel = read("errmodel.default.hfst").map_err(Error::ArchiveError)?;
+        let metadata = match read("index.xml") {
+            Ok(xml) => SpellerMetadata::parse(&String::from_utf8_lossy(&xml)),
+            Err(zip::result::ZipError::FileNotFound) => SpellerMetadata::default(),
+            Err(e) => return Err(Error::ArchiveError(e)),
+        };
+
+        let mut speller = Speller::new(
+            crate::read_transducer(&acceptor)?,
+            crate::read_transducer(&errmodel)?,
+        )?;
+        speller.metadata = metadata;
+        Ok(speller)
+    }
+
+    pub fn metadata(&self) -> &SpellerMetadata {
+        &self.metadata
+    }
+
+    pub fn acceptor(&self) -> &BoxedTransducer {
+        self.composed.second()
+    }
+
+    pub fn errmodel(&self) -> &BoxedTransducer {
+        self.composed.first()
+    }
+
+    /// Sets the maximum weight of suggestions.  Paths heavier than it are
+    /// not explored.
+    pub fn set_max_weight(&mut self, weight: Option<Weight>) {
+        self.max_weight = weight;
+    }
+
+    /// Sets how many search states [`Speller::suggest()`] may expand before
+    /// giving up, which bounds the time spent on cyclic error models.
+    ///
+    /// `limit == 0` means no limit is imposed.
+    pub fn set_search_limit(&mut self, limit: usize) {
+        self.search_limit = limit;
+    }
+
+    /// Checks whether the acceptor recognizes the word.
+    pub fn is_correct(&self, word: &str) -> bool {
+        let options = LookupOptions {
+            max_analyses: 1,
+            ..self.acceptor().options().clone()
+        };
+        self.acceptor().tokenize(word)
+            .and_then(|input| self.acceptor().lookup_encoded_with(&input, &options))
+            .is_ok_and(|analyses| !analyses.is_empty())
+    }
+
+    /// Returns up to `n` corrections of the word, lightest first.
+    ///
+    /// `n == 0` means all corrections are returned.  A correction reached by
+    /// several paths of the error model is returned once.  Suggestions found
+    /// before the search limit is reached are kept.
+    pub fn suggest(&self, word: &str, n: usize) -> Vec<(String, Weight)> {
+        let options = LookupOptions {
+            best_first: true,
+            max_weight: self.max_weight,
+            max_steps: self.search_limit,
+            ..LookupOptions::default()
+        };
+        let mut suggestions: Vec<(String, Weight)> = vec![];
+        // Errors only stop the search, keeping the suggestions found so far
+        let _ = self.composed.lookup_each(word, &options, &mut |suggestion, weight| {
+            if suggestions.iter().all(|(seen, _)| *seen != suggestion) {
+                suggestions.push((suggestion, weight));
+            }
+            if suggestions.len() == n {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use crate::compile_att;
+
+    fn speller() -> Speller {
+        let acceptor = compile_att(
+            "0\t1\tc\tc\n1\t2\ta\ta\n1\t3\tu\tu\n2\t4\tt\tt\n3\t4\tt\tt\n2\t4\tr\tr\n4\n"
+        ).unwrap();
+        let mut errmodel = String::new();
+        for c in ["a", "c", "r", "t", "u"] {
+            errmodel.push_str(&alloc::format!("0\t0\t{c}\t{c}\n"));
+        }
+        errmodel.push_str("0\t0\tx\ta\t1\n0\t0\tx\tu\t2\n0\t0\t@0@\tr\t3\n0\n");
+        let errmodel = compile_att(&errmodel).unwrap();
+        Speller::new(Box::new(acceptor), Box::new(errmodel)).unwrap()
+    }
+
+    #[test]
+    fn suggestions() {
+        let speller = speller();
+        assert!(speller.is_correct("cat"));
+        assert!(!speller.is_correct("cxt"));
+        assert_eq!(speller.suggest("cxt", 0), vec![
+            (String::from("cat"), 1.0),
+            (String::from("cut"), 2.0),
+        ]);
+        assert_eq!(speller.suggest("cx", 1), vec![(String::from("car"), 4.0)]);
+        assert!(speller.suggest("cqt", 5).is_empty());
+    }
+
+    #[test]
+    fn metadata() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<hfstspeller dtdversion="1.0" hfstversion="3">
+  <info>
+    <locale>udm</locale>
+    <title xml:lang="en">Udmurt speller</title>
+    <title xml:lang="udm">Удмурт</title>
+    <version vcsrev="42">1.0</version>
+    <contact email="a@b.c"/>
+    <producer>Tom &amp; Jerry</producer>
+  </info>
+  <acceptor type="general" id="acceptor.default.hfst">
+    <title>Acceptor</title>
+  </acceptor>
+</hfstspeller>"#;
+        assert_eq!(SpellerMetadata::parse(xml), SpellerMetadata {
+            locale: Some(String::from("udm")),
+            title: Some(String::from("Udmurt speller")),
+            description: None,
+            version: Some(String::from("1.0")),
+            date: None,
+            producer: Some(String::from("Tom & Jerry")),
+        });
+    }
+
+    #[cfg(feature = "zhfst")]
+    #[test]
+    fn zhfst_archive() {
+        use std::io::Write;
+        use crate::transducers::{WriteOptions, write_transducer};
+
+        let speller = speller();
+        let options = WriteOptions::default();
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        let file_options = zip::write::SimpleFileOptions::default();
+        for (name, content) in [
+            ("acceptor.default.hfst", write_transducer(speller.acceptor().as_ref(), &options).unwrap()),
+            ("errmodel.default.hfst", write_transducer(speller.errmodel().as_ref(), &options).unwrap()),
+            ("index.xml", b"<hfstspeller><info><locale>en</locale></info></hfstspeller>".to_vec()),
+        ] {
+            zip.start_file(name, file_options).unwrap();
+            zip.write_all(&content).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+
+        let speller = Speller::from_zhfst(&archive).unwrap();
+        assert_eq!(speller.metadata().locale.as_deref(), Some("en"));
+        assert_eq!(speller.suggest("cxt", 1), vec![(String::from("cat"), 1.0)]);
+    }
+}
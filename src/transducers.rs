@@ -1,32 +1,66 @@
 use alloc::{
     boxed::Box,
     vec::Vec, vec,
-    string::String,
+    string::{String, ToString},
 };
+use core::ops::{ControlFlow, Range};
 
 mod header;
 mod alphabet;
-mod cursor;
+mod flags;
 mod transitions;
-mod weighted;
+mod options;
+mod analysis;
+mod lookup;
+mod table;
+mod mapped;
+mod writer;
+mod basic;
+mod att;
+mod composed;
 
-use self::header::Header;
-use self::alphabet::Alphabet;
-use self::transitions::{TransitionIndex, WeightedTransition};
+
+pub use self::header::{Header, Hfst3Header};
+pub use self::alphabet::{Alphabet, SymbolKind};
+pub use self::options::{LookupOptions, EpsilonCycles, CaseVariant, DEFAULT_MAX_OUTPUT_LENGTH};
+#[cfg(target_has_atomic = "ptr")]
+pub use self::options::CancelHook;
+use self::options::Restoration;
+pub use self::analysis::{Analysis, AnalysisSymbol, LookupReport, SymbolPair};
+use self::lookup::Lookup;
+pub use self::flags::{FlagDiacriticOperator, FlagDiacriticOperation};
+pub use self::table::{TableTransducer, WeightedTransducer, UnweightedTransducer};
+pub use self::mapped::MappedTransducer;
+pub use self::transitions::{
+    TransitionIndex, WeightedTransition, UnweightedTransition, TargetEntry, TransitionTables,
+};
+pub use self::writer::{WriteOptions, write_transducer};
+pub use self::basic::{BasicTransducer, BasicState, BasicTransition, StateNumber};
+pub use self::composed::ComposedTransducer;
 use crate::trie::Trie;
 use crate::parser_utils::parse_to_vec_n;
+use nom::combinator::opt;
 
 pub type KeyTable = Vec<String>;
 pub type Symbol = u16;
 pub type TransitionTableIndex = u32;
 pub type Weight = f32;
+/// Input and output symbols of the transitions on a path.
+pub type EncodedPath = Vec<(Symbol, Symbol)>;
+/// Encoded outputs with their weights, as found by a lookup.
+pub type EncodedAnalyses = Vec<(Vec<Symbol>, Weight)>;
 
 pub const NO_SYMBOL_NUMBER: Symbol = Symbol::MAX;
 pub const EPSILON: Symbol = 0;
 pub const NO_TABLE_INDEX: TransitionTableIndex = TransitionTableIndex::MAX;
 pub const TRANSITION_TARGET_TABLE_START: TransitionTableIndex = 1 << 31;
 
+/// Errors of this crate.
+///
+/// Some variants exist only with some features, so the enum is not
+/// exhaustive: features enabled by other crates could add variants.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     HeaderParsingError,
     SymbolTableParsingError,
@@ -34,21 +68,142 @@ pub enum Error {
     TokenizationError,
     DecodingError,
     UnsupportedTransducerError,
-    SyncError,
+    /// An HFST3 header is too large to be written.
+    HeaderWritingError,
+    /// The HFST3 header declares a type of transducer this crate cannot read.
+    UnsupportedTransducerType(String),
+    /// Malformed line in AT&T text, numbered from 1.
+    AttParsingError(usize),
+    #[cfg(feature = "mmap")]
+    IoError(std::io::Error),
+    /// A `.zhfst` archive could not be read or lacks a transducer.
+    #[cfg(feature = "zhfst")]
+    ArchiveError(zip::result::ZipError),
+    /// A lookup explored more paths than
+    /// [`LookupOptions::max_steps`] allows.
+    BudgetExceeded,
+    /// A lookup was stopped by [`LookupOptions::cancel`].
+    Cancelled,
+    /// A path produced an output longer than
+    /// [`LookupOptions::max_output_length`].
+    OutputLimitExceeded,
+    /// A path went around an input-epsilon cycle, which
+    /// [`EpsilonCycles::Fail`] does not allow.
+    EpsilonCycle,
 }
 
 /// Trait all transducers should implement.
 ///
 /// Run [`Transducer::lookup()`] with a `&str` to get analyses for the string.
 pub trait Transducer {
-    fn key_table(&self) -> &KeyTable;
+    fn alphabet(&self) -> &Alphabet;
     fn input_letters(&self) -> &Trie<Symbol>;
-    
+
+    /// Options used by [`Transducer::lookup()`] and
+    /// [`Transducer::lookup_encoded()`].
+    fn options(&self) -> &LookupOptions;
+    fn options_mut(&mut self) -> &mut LookupOptions;
+
+    /// Peforms a lookup of pre-encoded string with the given options.
+    ///
+    /// See [`Transducer::lookup_with()`].
+    fn lookup_encoded_with(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<Symbol>, Weight)>, Error>;
+
+    /// Performs a lookup of pre-encoded string, passing analyses to `f` as
+    /// they are found.  The search stops as soon as `f` returns
+    /// [`ControlFlow::Break`].
+    ///
+    /// The default implementation runs the whole lookup first.
+    fn lookup_encoded_each(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+        f: &mut dyn FnMut(Vec<Symbol>, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        for (output, weight) in self.lookup_encoded_with(input, options)? {
+            if f(output, weight).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs a lookup of pre-encoded string like
+    /// [`Transducer::lookup_encoded_with()`], also returning the number of
+    /// paths cut at input-epsilon cycles.
+    ///
+    /// The default implementation searches [`Transducer::tables()`].
+    /// Transducers without tables report no cuts.
+    fn lookup_encoded_report(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<(EncodedAnalyses, usize), Error> {
+        let Some(tables) = self.tables() else {
+            return self.lookup_encoded_with(input, options).map(|analyses| (analyses, 0));
+        };
+        let mut lookup = Lookup::new(tables, self.alphabet(), options);
+        if let Some(header) = self.header() {
+            lookup = lookup.with_header(header);
+        }
+        let analyses = lookup.run(input)?;
+        Ok((analyses, lookup.cycles_cut()))
+    }
+
+    /// Performs a lookup of pre-encoded string, returning the input and
+    /// output symbols of every transition on the paths.
+    ///
+    /// See [`Transducer::lookup_aligned()`].  The default implementation
+    /// searches [`Transducer::tables()`], and returns
+    /// [`Error::UnsupportedTransducerError`] if there are none.
+    fn lookup_encoded_aligned(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(EncodedPath, Weight)>, Error> {
+        let tables = self.tables().ok_or(Error::UnsupportedTransducerError)?;
+        let mut lookup = Lookup::new(tables, self.alphabet(), options);
+        if let Some(header) = self.header() {
+            lookup = lookup.with_header(header);
+        }
+        lookup.run_aligned(input)
+    }
+
+    fn key_table(&self) -> &KeyTable {
+        self.alphabet().key_table()
+    }
+
+    /// Header of the binary transducer, if the implementation was read from
+    /// one.
+    fn header(&self) -> Option<&Header> {
+        None
+    }
+
+    /// Transition tables of the transducer, if it is backed by them.
+    fn tables(&self) -> Option<&dyn TransitionTables> {
+        None
+    }
+
+    /// HFST3 metadata, such as name and version, of the transducer.
+    fn hfst3_header(&self) -> Option<&Hfst3Header> {
+        self.header().and_then(Header::hfst3)
+    }
+
     /// Peforms a lookup of pre-encoded string.
     ///
     /// See [`Transducer::lookup()`].
-    fn lookup_encoded(&self, input: &[Symbol]) -> Result<Vec<(Vec<Symbol>, Weight)>, Error>;
+    fn lookup_encoded(&self, input: &[Symbol]) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        self.lookup_encoded_with(input, self.options())
+    }
 
+    /// Splits the input into input symbols.
+    ///
+    /// Fails with [`Error::TokenizationError`] on characters out of the
+    /// alphabet, see [`Transducer::tokenize_with_unknown()`] for keeping them.
     fn tokenize(&self, mut input: &str) -> Result<Vec<Symbol>, Error> {
         let mut res = vec![];
         let trie = self.input_letters();
@@ -64,6 +219,26 @@ pub trait Transducer {
         Ok(res)
     }
 
+    /// Number of the first unknown symbol made by
+    /// [`Transducer::tokenize_with_unknown()`].  It is past all the symbols of
+    /// the transducer.
+    fn unknown_symbols_start(&self) -> Symbol {
+        self.key_table().len() as Symbol
+    }
+
+    /// Splits the input into input symbols, turning characters out of the
+    /// alphabet into unknown symbols.
+    ///
+    /// Every distinct unknown character gets a number from
+    /// [`Transducer::unknown_symbols_start()`] on, and the characters are
+    /// returned in order of their numbers.  Lookup follows
+    /// `@_IDENTITY_SYMBOL_@` and `@_UNKNOWN_SYMBOL_@` transitions on them,
+    /// and identity transitions copy them to the output.  Outputs can be
+    /// decoded with [`Transducer::decode_with_unknown()`].
+    fn tokenize_with_unknown(&self, input: &str) -> Result<(Vec<Symbol>, KeyTable), Error> {
+        tokenize_spans(self, input).map(|(symbols, _, unknown)| (symbols, unknown))
+    }
+
     /// Decodes an encoded string, writing to the given [`&mut String`].
     fn decode_to(&self, mut input: &[Symbol], result: &mut String) -> Result<(), Error> {
         let kt = self.key_table();
@@ -83,22 +258,210 @@ pub trait Transducer {
         Ok(result)
     }
     
+    /// Decodes an encoded string which may contain unknown symbols, given the
+    /// characters returned by [`Transducer::tokenize_with_unknown()`].
+    fn decode_with_unknown(&self, input: &[Symbol], unknown: &[String]) -> Result<String, Error> {
+        let mut result = String::new();
+        for &sym in input {
+            result.push_str(symbol_name(self, sym, unknown)?);
+        }
+        Ok(result)
+    }
+
     /// Performs a lookup of the given string.
     ///
     /// Returns a vector of tuples of resulting strings and weights.  If the
     /// transducer is unweighted, all the weights are 0.0.
     ///
+    /// Paths going around input-epsilon cycles are cut by default, without
+    /// telling.  See [`LookupOptions::epsilon_cycles`], and
+    /// [`Transducer::lookup_report()`] for the number of cut paths.
+    ///
     /// See [`Transducer::lookup_encoded()`] for looking up pre-encoded strings.
     fn lookup(&self, input: &str) -> Result<Vec<(String, Weight)>, Error> {
-        let tokens = self.tokenize(input)?;
-        self.lookup_encoded(&tokens)
-            .and_then(|analyses| {
-                analyses.into_iter()
-                    .try_fold(vec![], |mut v, analysis| {
-                        v.push((self.decode(&analysis.0)?, analysis.1));
-                        Ok(v)
+        self.lookup_with(input, self.options())
+    }
+
+    /// Performs a lookup of the given string, using `options` instead of the
+    /// ones set for the transducer.
+    fn lookup_with(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+    ) -> Result<Vec<(String, Weight)>, Error> {
+        self.lookup_cased(input, options).map(|(_, analyses)| analyses)
+    }
+
+    /// Performs a lookup like [`Transducer::lookup_with()`], also returning
+    /// the case variant of the input which gave the analyses.  Without
+    /// [`LookupOptions::case_fallback`] it is always the input as given.
+    ///
+    /// ```
+    /// use hfstol::{LookupOptions, Transducer, compile_att};
+    /// use hfstol::transducers::CaseVariant;
+    ///
+    /// let t = compile_att("0\t1\tк\tк\n1\t2\tо\tо\n2\t3\t@0@\t+N\n3\n").unwrap();
+    /// let options = LookupOptions {
+    ///     case_fallback: true,
+    ///     ..LookupOptions::default()
+    /// };
+    /// assert_eq!(
+    ///     t.lookup_cased("Ко", &options).unwrap(),
+    ///     (CaseVariant::FirstLowercased, vec![("ко+N".to_string(), 0.0)]),
+    /// );
+    /// ```
+    fn lookup_cased(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+    ) -> Result<(CaseVariant, Vec<(String, Weight)>), Error> {
+        lookup_strings(self, input, options).map(|(variant, analyses, _)| (variant, analyses))
+    }
+
+    /// Performs a lookup like [`Transducer::lookup_with()`], also returning
+    /// the number of paths cut at input-epsilon cycles, see
+    /// [`LookupOptions::epsilon_cycles`].
+    ///
+    /// ```
+    /// use hfstol::{Transducer, compile_att};
+    ///
+    /// let t = compile_att("0\t1\ta\tb\n1\t2\t@0@\tc\n2\t1\t@0@\td\n1\n").unwrap();
+    /// let report = t.lookup_report("a", t.options()).unwrap();
+    /// assert_eq!(report.analyses, vec![("b".to_string(), 0.0)]);
+    /// assert_eq!(report.cycles_cut, 1);
+    /// ```
+    fn lookup_report(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+    ) -> Result<LookupReport, Error> {
+        let (_, analyses, cycles_cut) = lookup_strings(self, input, options)?;
+        Ok(LookupReport { analyses, cycles_cut })
+    }
+
+    /// Performs a lookup like [`Transducer::lookup_with()`], keeping the
+    /// symbols of the analyses apart.  See [`Analysis`].
+    fn lookup_analyses(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+    ) -> Result<Vec<Analysis>, Error> {
+        lookup_variants(
+            self,
+            input,
+            options,
+            |output, weight, unknown, restoration| {
+                let mut analysis = decode_analysis(self, &output, weight, unknown)?;
+                analysis.restore_case(restoration);
+                Ok(analysis)
+            },
+        ).map(|(_, analyses, _)| analyses)
+    }
+
+    /// Performs a lookup of the given string, passing analyses to `f` as they
+    /// are found.  Callers that need only some of the analyses do not pay for
+    /// the full search.
+    ///
+    /// Like [`Transducer::lookup()`], paths cut at input-epsilon cycles are
+    /// not reported.
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use hfstol::{Transducer, compile_att};
+    ///
+    /// let t = compile_att("0\t1\ta\tx\n0\t1\ta\ty\n1\n").unwrap();
+    /// let mut first = None;
+    /// t.lookup_each("a", t.options(), &mut |analysis, _| {
+    ///     first = Some(analysis);
+    ///     ControlFlow::Break(())
+    /// }).unwrap();
+    /// assert_eq!(first.as_deref(), Some("x"));
+    /// ```
+    fn lookup_each(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+        f: &mut dyn FnMut(String, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        try_variants(self, input, options, |tokens, unknown, restoration| {
+            let mut found = false;
+            let mut result = Ok(());
+            self.lookup_encoded_each(tokens, options, &mut |output, weight| {
+                found = true;
+                match decode_restored(self, &output, unknown, restoration) {
+                    Ok(analysis) => f(analysis, weight),
+                    Err(e) => {
+                        result = Err(e);
+                        ControlFlow::Break(())
+                    },
+                }
+            })?;
+            result.map(|()| found)
+        }).map(|_| ())
+    }
+
+    /// Performs a lookup of the given string, returning the input and output
+    /// symbols of every transition on the path of each analysis.
+    ///
+    /// Epsilons are kept on both sides, and every pair has the byte span of
+    /// its input symbol in `input`, so that parts of the output can be traced
+    /// back to the part of the word which produced them.
+    /// [`LookupOptions::case_fallback`] is not applied.
+    ///
+    /// ```
+    /// use hfstol::{Transducer, compile_att};
+    ///
+    /// let t = compile_att("0\t1\tк\tк\n1\t2\tо\tо\n2\t3\t@0@\t+N\n3\n").unwrap();
+    /// let (path, _) = &t.lookup_aligned("ко", t.options()).unwrap()[0];
+    /// let pairs: Vec<_> = path.iter()
+    ///     .map(|pair| (pair.input.as_str(), pair.output.as_str(), pair.span.clone()))
+    ///     .collect();
+    /// assert_eq!(pairs, [("к", "к", 0..2), ("о", "о", 2..4), ("", "+N", 4..4)]);
+    /// ```
+    fn lookup_aligned(
+        &self,
+        input: &str,
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<SymbolPair>, Weight)>, Error> {
+        let (tokens, spans, unknown) = tokenize_spans(self, input)?;
+        self.lookup_encoded_aligned(&tokens, options)?
+            .into_iter()
+            .map(|(path, weight)| {
+                let mut consumed = spans.iter();
+                let pairs = path.into_iter()
+                    .map(|(i, o)| {
+                        let span = if i == EPSILON || self.alphabet().is_flag(i) {
+                            let position = consumed.as_slice().first().map_or(input.len(), |span| span.start);
+                            position..position
+                        } else {
+                            consumed.next().cloned().ok_or(Error::DecodingError)?
+                        };
+                        Ok(SymbolPair {
+                            input: String::from(symbol_name(self, i, &unknown)?),
+                            output: String::from(symbol_name(self, o, &unknown)?),
+                            span,
+                        })
                     })
+                    .collect::<Result<_, Error>>()?;
+                Ok((pairs, weight))
             })
+            .collect()
+    }
+
+    /// Builds the inverse transducer, which maps outputs back to inputs.
+    ///
+    /// For an analyser this gives a generator: its index and target tables
+    /// are keyed on analyses, and its input tokenizer knows multicharacter
+    /// tags, so strings like `лэсьтӥськон+N+Pl+Nom` can be looked up.
+    ///
+    /// The tables are unpacked and packed again, in time linear in their size.
+    ///
+    /// Returns [`Error::UnsupportedTransducerError`] if the transducer is not
+    /// backed by transition tables.
+    fn invert(&self) -> Result<WeightedTransducer, Error> {
+        let mut basic = BasicTransducer::from_transducer(self)?;
+        basic.invert();
+        basic.compile()
     }
 
     /// Sets a maximum count of analyses performed.
@@ -107,36 +470,479 @@ pub trait Transducer {
     /// the results vector of `Transducer::lookup()`.
     ///
     /// `count == 0` means no limit is imposed.
-    fn set_max_analyses(&mut self, count: usize);
+    fn set_max_analyses(&mut self, count: usize) {
+        self.options_mut().max_analyses = count;
+    }
 }
 
-/// Reads a binary transducer and initializes an appropriate implementation.
+impl<T: Transducer + ?Sized> Transducer for Box<T> {
+    fn alphabet(&self) -> &Alphabet {
+        (**self).alphabet()
+    }
+
+    fn input_letters(&self) -> &Trie<Symbol> {
+        (**self).input_letters()
+    }
+
+    fn options(&self) -> &LookupOptions {
+        (**self).options()
+    }
+
+    fn options_mut(&mut self) -> &mut LookupOptions {
+        (**self).options_mut()
+    }
+
+    fn lookup_encoded_with(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        (**self).lookup_encoded_with(input, options)
+    }
+
+    fn lookup_encoded_each(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+        f: &mut dyn FnMut(Vec<Symbol>, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        (**self).lookup_encoded_each(input, options, f)
+    }
+
+    fn lookup_encoded_report(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<(EncodedAnalyses, usize), Error> {
+        (**self).lookup_encoded_report(input, options)
+    }
+
+    fn lookup_encoded_aligned(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(EncodedPath, Weight)>, Error> {
+        (**self).lookup_encoded_aligned(input, options)
+    }
+
+    fn unknown_symbols_start(&self) -> Symbol {
+        (**self).unknown_symbols_start()
+    }
+
+    fn header(&self) -> Option<&Header> {
+        (**self).header()
+    }
+
+    fn tables(&self) -> Option<&dyn TransitionTables> {
+        (**self).tables()
+    }
+}
+
+/// Input symbols with their byte spans, and strings of unknown symbols.
+type Tokens = (Vec<Symbol>, Vec<Range<usize>>, KeyTable);
+
+/// Splits the input like [`Transducer::tokenize_with_unknown()`], also
+/// returning the byte span of every symbol.
+fn tokenize_spans<T: Transducer + ?Sized>(
+    t: &T,
+    input: &str,
+) -> Result<Tokens, Error> {
+    let start = t.unknown_symbols_start() as usize;
+    let mut res = vec![];
+    let mut spans = vec![];
+    let mut unknown: KeyTable = vec![];
+    let trie = t.input_letters();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let offset = input.len() - rest.len();
+        match trie.get(rest) {
+            (Some(sym), after) => {
+                res.push(*sym);
+                rest = after;
+            },
+            _ => {
+                let len = rest.chars().next().map_or(0, char::len_utf8);
+                let (c, after) = rest.split_at(len);
+                let k = match unknown.iter().position(|u| u == c) {
+                    Some(k) => k,
+                    None => {
+                        unknown.push(String::from(c));
+                        unknown.len() - 1
+                    },
+                };
+                let sym = Symbol::try_from(start + k).ok()
+                    .filter(|&sym| sym != NO_SYMBOL_NUMBER)
+                    .ok_or(Error::TokenizationError)?;
+                res.push(sym);
+                rest = after;
+            },
+        }
+        spans.push(offset..input.len() - rest.len());
+    }
+    Ok((res, spans, unknown))
+}
+
+/// Returns the string of a symbol, which may be one of the unknown symbols
+/// made by [`Transducer::tokenize_with_unknown()`].
+fn symbol_name<'a, T: Transducer + ?Sized>(
+    t: &'a T,
+    symbol: Symbol,
+    unknown: &'a [String],
+) -> Result<&'a str, Error> {
+    let start = t.unknown_symbols_start() as usize;
+    let symbol = symbol as usize;
+    let name = match symbol.checked_sub(start) {
+        Some(k) => unknown.get(k),
+        None => t.key_table().get(symbol),
+    };
+    name.map(String::as_str).ok_or(Error::DecodingError)
+}
+
+/// Decodes an output into an [`Analysis`].
+fn decode_analysis<T: Transducer + ?Sized>(
+    t: &T,
+    output: &[Symbol],
+    weight: Weight,
+    unknown: &[String],
+) -> Result<Analysis, Error> {
+    let symbols = output.iter()
+        .map(|&sym| symbol_name(t, sym, unknown).map(AnalysisSymbol::new))
+        .filter_map(Result::transpose)
+        .collect::<Result<_, _>>()?;
+    Ok(Analysis::new(symbols, weight))
+}
+
+/// Decodes an output into a string, changing the case of its symbols other
+/// than tags.
+fn decode_restored<T: Transducer + ?Sized>(
+    t: &T,
+    output: &[Symbol],
+    unknown: &[String],
+    restoration: Restoration,
+) -> Result<String, Error> {
+    if restoration == Restoration::Keep {
+        return t.decode_with_unknown(output, unknown);
+    }
+    let mut analysis = decode_analysis(t, output, 0.0, unknown)?;
+    analysis.restore_case(restoration);
+    Ok(analysis.to_string())
+}
+
+/// Tries case variants of the input in order, until one has analyses.
 ///
-/// Returns [`Error::UnsupportedTransducerError`] if no transducer could be
-/// created.
-pub fn read_transducer(input: &[u8]) -> Result<Box<dyn Transducer + Sync + Send>, Error> {
-    let (input, header) = Header::parse(input)
+/// `lookup` is given the symbols of a variant, its unknown characters, and
+/// how to restore the case of the input in its outputs if
+/// [`LookupOptions::restore_case`] is set.  It tells whether the variant had
+/// analyses.  Returns the variant which had them, or the input as given if
+/// none had.
+fn try_variants<T, F>(
+    t: &T,
+    input: &str,
+    options: &LookupOptions,
+    mut lookup: F,
+) -> Result<CaseVariant, Error>
+where
+    T: Transducer + ?Sized,
+    F: FnMut(&[Symbol], &[String], Restoration) -> Result<bool, Error>,
+{
+    for (variant, candidate) in CaseVariant::variants(input, options.case_fallback) {
+        let restoration = if options.restore_case {
+            variant.restoration(input)
+        } else {
+            Restoration::Keep
+        };
+        let (tokens, unknown) = t.tokenize_with_unknown(&candidate)?;
+        if lookup(&tokens, &unknown, restoration)? {
+            return Ok(variant);
+        }
+    }
+    Ok(CaseVariant::AsGiven)
+}
+
+/// Case variant of the input with its analyses, and the number of paths cut
+/// at input-epsilon cycles.
+type VariantAnalyses<A> = (CaseVariant, Vec<A>, usize);
+
+/// Looks up case variants of the input like [`try_variants()`], decoding
+/// outputs with `decode`.
+///
+/// Paths cut at input-epsilon cycles are counted in all the tried variants.
+fn lookup_variants<T, A, D>(
+    t: &T,
+    input: &str,
+    options: &LookupOptions,
+    decode: D,
+) -> Result<VariantAnalyses<A>, Error>
+where
+    T: Transducer + ?Sized,
+    D: Fn(Vec<Symbol>, Weight, &[String], Restoration) -> Result<A, Error>,
+{
+    let mut cycles_cut = 0;
+    let mut analyses = Vec::new();
+    let variant = try_variants(t, input, options, |tokens, unknown, restoration| {
+        let (found, cut) = t.lookup_encoded_report(tokens, options)?;
+        cycles_cut += cut;
+        analyses = found.into_iter()
+            .map(|(output, weight)| decode(output, weight, unknown, restoration))
+            .collect::<Result<_, _>>()?;
+        Ok(!analyses.is_empty())
+    })?;
+    Ok((variant, analyses, cycles_cut))
+}
+
+/// Looks up case variants of the input like [`lookup_variants()`], decoding
+/// outputs into strings.
+fn lookup_strings<T: Transducer + ?Sized>(
+    t: &T,
+    input: &str,
+    options: &LookupOptions,
+) -> Result<VariantAnalyses<(String, Weight)>, Error> {
+    lookup_variants(t, input, options, |output, weight, unknown, restoration| {
+        Ok((decode_restored(t, &output, unknown, restoration)?, weight))
+    })
+}
+
+pub type BoxedTransducer = Box<dyn Transducer + Sync + Send>;
+
+/// Parses everything preceding the transition tables.
+///
+/// Returns the rest of the input, header, alphabet and whether the transducer
+/// is weighted.
+///
+/// Weightedness is taken from the type declared in the HFST3 header, or from
+/// the binary header if the type is not declared.
+fn parse_prologue(input: &[u8]) -> Result<(&[u8], Header, Alphabet, bool), Error> {
+    let (input, hfst3) = opt(Hfst3Header::parse)(input)
+        .map_err(|_: nom::Err<nom::error::Error<&[u8]>>| Error::HeaderParsingError)?;
+    let declared = match hfst3.as_ref().and_then(Hfst3Header::transducer_type) {
+        None => None,
+        Some("HFST_OLW") => Some(true),
+        Some("HFST_OL") => Some(false),
+        Some(other) => return Err(Error::UnsupportedTransducerType(String::from(other))),
+    };
+    let (input, header) = Header::parse(input, hfst3)
         .map_err(|_| Error::HeaderParsingError)?;
+    let weighted = declared.unwrap_or(header.weighted());
     let (input, alphabet) = Alphabet::parse(input, header.number_of_symbols())
         .map_err(|_| Error::SymbolTableParsingError)?;
+    Ok((input, header, alphabet, weighted))
+}
+
+/// Parses one transducer, returning it with the rest of the input.
+fn parse_transducer(input: &[u8]) -> Result<(&[u8], BoxedTransducer), Error> {
+    let (input, header, alphabet, weighted) = parse_prologue(input)?;
+    if weighted {
+        parse_tables::<WeightedTransition>(input, header, alphabet)
+    } else {
+        parse_tables::<UnweightedTransition>(input, header, alphabet)
+    }
+}
+
+/// Parses the transition tables following the symbol table.
+fn parse_tables<T: TargetEntry + Send + Sync + 'static>(
+    input: &[u8],
+    header: Header,
+    alphabet: Alphabet,
+) -> Result<(&[u8], BoxedTransducer), Error> {
     let (input, index) = parse_to_vec_n(
         header.size_of_transition_index_table() as usize,
         TransitionIndex::parse,
     )(input).map_err(|_| Error::TableParsingError)?;
+    let (input, transitions) = parse_to_vec_n(
+        header.size_of_transition_target_table() as usize,
+        T::parse,
+    )(input).map_err(|_| Error::TableParsingError)?;
+    Ok((input, Box::new(TableTransducer::new(header, alphabet, index, transitions))))
+}
+
+
+/// Reads a binary transducer and initializes an appropriate implementation.
+///
+/// The implementation is picked from the type declared in the HFST3 header:
+/// `HFST_OLW` for weighted and `HFST_OL` for unweighted transducers.  Other
+/// types are rejected with [`Error::UnsupportedTransducerType`].  Transducers
+/// without an HFST3 header are read according to their header properties.
+///
+/// Only the first transducer of the input is read.  See [`read_transducers()`]
+/// for reading streams of several transducers.
+pub fn read_transducer(input: &[u8]) -> Result<BoxedTransducer, Error> {
+    parse_transducer(input).map(|(_, transducer)| transducer)
+}
+
+/// Transducer read from a stream by [`read_transducers()`].
+pub struct StreamEntry {
+    pub transducer: BoxedTransducer,
+    /// Position of the transducer in the stream, in bytes.
+    pub offset: usize,
+    /// Number of bytes the transducer took, including its HFST3 header.
+    pub size: usize,
+}
+
+impl StreamEntry {
+    /// HFST3 metadata of the transducer.
+    pub fn hfst3_header(&self) -> Option<&Hfst3Header> {
+        self.transducer.hfst3_header()
+    }
+}
+
+/// Reads all transducers concatenated in a single stream, as written by
+/// `hfst-split` and other HFST tools.
+///
+/// Each transducer is read as by [`read_transducer()`].  Fails if any part of
+/// the input is not a transducer.
+pub fn read_transducers(input: &[u8]) -> Result<Vec<StreamEntry>, Error> {
+    let mut res = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (next, transducer) = parse_transducer(rest)?;
+        res.push(StreamEntry {
+            transducer,
+            offset: input.len() - rest.len(),
+            size: rest.len() - next.len(),
+        });
+        rest = next;
+    }
+    Ok(res)
+}
+
+/// Compiles a transducer in AT&T text format for lookup.
+///
+/// See [`BasicTransducer::from_att()`] for the format description.
+///
+/// ```
+/// use hfstol::Transducer;
+///
+/// let t = hfstol::compile_att("0\t1\tc\tc\n1\t2\ta\ta\n2\t3\tt\tt\n3\t4\t@0@\t+N\t0.5\n4\n").unwrap();
+/// assert_eq!(t.lookup("cat").unwrap(), vec![("cat+N".to_string(), 0.5)]);
+/// ```
+pub fn compile_att(text: &str) -> Result<WeightedTransducer, Error> {
+    BasicTransducer::from_att(text)?.compile()
+}
+
+/// Exports a transducer to AT&T text format, for inspection or for use with
+/// other toolkits.
+///
+/// Weights are printed if the transducer is weighted.  See
+/// [`BasicTransducer::from_transducer()`] for how states are numbered.
+pub fn export_att<T: Transducer + ?Sized>(transducer: &T) -> Result<String, Error> {
+    let weighted = transducer.tables()
+        .ok_or(Error::UnsupportedTransducerError)?
+        .weighted();
+    Ok(BasicTransducer::from_transducer(transducer)?.to_att(weighted))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Weighted transducer mapping `a` to `b` with weight 1.5.
+    pub(crate) fn a_to_b() -> WeightedTransducer {
+        let symbols = ["@_EPSILON_SYMBOL_@", "a", "b"];
+        let alphabet = Alphabet::new(symbols.iter().map(|&s| String::from(s)).collect());
+        let index = vec![
+            TransitionIndex::new(NO_SYMBOL_NUMBER, NO_TABLE_INDEX),
+            TransitionIndex::new(NO_SYMBOL_NUMBER, NO_TABLE_INDEX),
+            TransitionIndex::new(1, TRANSITION_TARGET_TABLE_START + 1),
+        ];
+        let transitions = vec![
+            WeightedTransition::new_final(0.5),
+            WeightedTransition::new(1, 2, TRANSITION_TARGET_TABLE_START, 1.0),
+        ];
+        let header = Header::new(2, 3, 3, 2, 2, 1, true);
+        WeightedTransducer::new(header, alphabet, index, transitions)
+    }
+
+    #[test]
+    fn shareable_between_threads() {
+        fn assert_shareable<T: Send + Sync>() {}
+        assert_shareable::<WeightedTransducer>();
+        assert_shareable::<UnweightedTransducer>();
+        assert_shareable::<MappedTransducer<&[u8]>>();
+    }
+
+    #[test]
+    fn case_fallback() {
+        let t = crate::compile_att("0\t1\ta\ta\n1\t2\tb\tb\n2\t3\t@0@\t+N\n3\n").unwrap();
+        let mut options = LookupOptions::default();
+        assert_eq!(t.lookup_with("AB", &options).unwrap(), vec![]);
+        options.case_fallback = true;
+        assert_eq!(t.lookup_cased("AB", &options).unwrap(), (
+            CaseVariant::Lowercased,
+            vec![(String::from("ab+N"), 0.0)],
+        ));
+
+        let generator = t.invert().unwrap();
+        options.restore_case = true;
+        assert_eq!(generator.lookup_with("Ab+N", &options).unwrap(), vec![(String::from("Ab"), 0.0)]);
+        assert_eq!(t.lookup_with("AB", &options).unwrap(), vec![(String::from("AB+N"), 0.0)]);
+        assert_eq!(generator.lookup_with("ab+N", &options).unwrap(), vec![(String::from("ab"), 0.0)]);
+
+        let t = crate::compile_att("0\t1\tа\tа\n1\t2\tб\tб\n2\t3\t@0@\t+Der/Он\n3\t4\t@0@\t+Pl\n4\n").unwrap();
+        let expected = vec![(String::from("АБ+Der/Он+Pl"), 0.0)];
+        assert_eq!(t.lookup_with("АБ", &options).unwrap(), expected);
+        assert_eq!(t.lookup_analyses("АБ", &options).unwrap()[0].to_string(), expected[0].0);
+        let mut streamed = vec![];
+        t.lookup_each("АБ", &options, &mut |analysis, weight| {
+            streamed.push((analysis, weight));
+            ControlFlow::Continue(())
+        }).unwrap();
+        assert_eq!(streamed, expected);
+
+        // Unbracketed tags keep their case too
+        let t = crate::compile_att("0\t1\tё\tё\n1\t2\t@0@\tNoun\n2\t3\t@0@\tSg\n3\n").unwrap();
+        let analyses = t.lookup_analyses("Ё", &options).unwrap();
+        assert_eq!(analyses[0].lemma(), "Ё");
+        assert_eq!(analyses[0].tags().collect::<Vec<_>>(), ["Noun", "Sg"]);
+        assert_eq!(t.lookup_with("Ё", &options).unwrap(), vec![(String::from("ЁNounSg"), 0.0)]);
+    }
+
+    #[test]
+    fn untyped_hfst3_header() {
+        let mut input = header::tests::hfst3_header(&[("version", "3.3")]);
+        input.extend(write_transducer(&a_to_b(), &WriteOptions {
+            weighted: true,
+            hfst3_header: false,
+        }).unwrap());
+        let t = read_transducer(&input).unwrap();
+        assert_eq!(t.hfst3_header().unwrap().version(), Some("3.3"));
+        assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), 1.5)]);
+    }
+
+    #[test]
+    fn transducer_stream() {
+        let weighted = write_transducer(&a_to_b(), &WriteOptions {
+            weighted: true,
+            hfst3_header: true,
+        }).unwrap();
+        let unweighted = write_transducer(&a_to_b(), &WriteOptions {
+            weighted: false,
+            hfst3_header: true,
+        }).unwrap();
+        let mut input = weighted.clone();
+        input.extend(&unweighted);
+
+        let entries = read_transducers(&input).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].offset, entries[0].size), (0, weighted.len()));
+        assert_eq!((entries[1].offset, entries[1].size), (weighted.len(), unweighted.len()));
+        assert_eq!(entries[0].hfst3_header().unwrap().transducer_type(), Some("HFST_OLW"));
+        assert_eq!(entries[1].hfst3_header().unwrap().transducer_type(), Some("HFST_OL"));
+        assert_eq!(entries[0].transducer.lookup("a").unwrap(), vec![(String::from("b"), 1.5)]);
+        assert_eq!(entries[1].transducer.lookup("a").unwrap(), vec![(String::from("b"), 0.0)]);
+
+        input.truncate(input.len() - 1);
+        assert!(matches!(read_transducers(&input), Err(Error::TableParsingError)));
+    }
 
-    // TODO: Other types of transducers
-    match (header.weighted(), alphabet.state_size() > 0) {
-        (true, false) => {
-            let (_input, transitions) = parse_to_vec_n(
-                header.size_of_transition_target_table() as usize,
-                WeightedTransition::parse
-            )(input).map_err(|_| Error::TableParsingError)?;
-            Ok(Box::new(
-                self::weighted::WeightedTransducer::new(
-                    header, alphabet, index, transitions,
-                )
-            ))
-        },
-        _ => Err(Error::UnsupportedTransducerError),
+    #[test]
+    fn unsupported_type() {
+        let input = header::tests::hfst3_header(&[
+            ("version", "3.3"),
+            ("type", "TROPICAL_OPENFST_TYPE"),
+        ]);
+        assert!(matches!(
+            read_transducer(&input),
+            Err(Error::UnsupportedTransducerType(t)) if t == "TROPICAL_OPENFST_TYPE"
+        ));
     }
 }
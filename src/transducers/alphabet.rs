@@ -1,6 +1,7 @@
 use alloc::{
     vec::Vec,
     string::String,
+    collections::BTreeMap,
 };
 use nom::{
     IResult,
@@ -8,50 +9,147 @@ use nom::{
     sequence::terminated,
 };
 
-use super::{KeyTable, Symbol};
+use super::{KeyTable, Symbol, EPSILON, NO_SYMBOL_NUMBER};
+use super::transitions::WeightedTransition;
+use super::flags::{Feature, Value, FlagDiacriticOperation};
 use crate::trie::Trie;
 
+/// Kind of a symbol in the symbol table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// An ordinary symbol, either a single character or a multicharacter one.
+    Regular,
+    /// `@_EPSILON_SYMBOL_@`, always the symbol number 0.
+    Epsilon,
+    /// `@_UNKNOWN_SYMBOL_@`.
+    Unknown,
+    /// `@_IDENTITY_SYMBOL_@`.
+    Identity,
+    /// `@_DEFAULT_SYMBOL_@`.
+    Default,
+    /// A flag diacritic, such as `@U.Case.Nom@`.
+    FlagDiacritic(FlagDiacriticOperation),
+}
+
+impl SymbolKind {
+    fn of_special(symbol: &str) -> Option<SymbolKind> {
+        match symbol {
+            "@_EPSILON_SYMBOL_@" => Some(SymbolKind::Epsilon),
+            "@_UNKNOWN_SYMBOL_@" => Some(SymbolKind::Unknown),
+            "@_IDENTITY_SYMBOL_@" => Some(SymbolKind::Identity),
+            "@_DEFAULT_SYMBOL_@" => Some(SymbolKind::Default),
+            _ => None,
+        }
+    }
+}
+
+/// Symbol table of a transducer.
+///
+/// All the symbols are kept at their original numbers, so the key table can
+/// be indexed with symbols from the transition tables.
 pub struct Alphabet {
     key_table: KeyTable,
-    state_size: Symbol,
+    kinds: Vec<SymbolKind>,
+    state_size: Feature,
+    identity: Option<Symbol>,
+    unknown: Option<Symbol>,
+}
+
+/// Assigns numbers to feature and value names of flag diacritics.
+struct FlagNames<'a> {
+    features: BTreeMap<&'a str, Feature>,
+    values: BTreeMap<&'a str, Value>,
+}
+
+impl<'a> FlagNames<'a> {
+    fn new() -> Self {
+        FlagNames {
+            features: BTreeMap::new(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    fn parse(&mut self, symbol: &'a str) -> Option<FlagDiacriticOperation> {
+        let (operator, feature, value) = FlagDiacriticOperation::split(symbol)?;
+        let next_feature = self.features.len() as Feature;
+        let feature = *self.features.entry(feature).or_insert(next_feature);
+        let value = match value {
+            Some(value) => {
+                // 0 is reserved for the neutral value
+                let next_value = self.values.len() as Value + 1;
+                *self.values.entry(value).or_insert(next_value)
+            },
+            None => 0,
+        };
+        Some(FlagDiacriticOperation::new(operator, feature, value))
+    }
 }
 
 impl Alphabet {
-    fn parse_flag_diacritic(input: &[u8]) -> bool {
-        // TODO: Parse flag diacritics properly
-        input.len() > 4 &&
-            input[0] == b'@' &&
-            input[input.len()-1] == b'@'
-            && input[2] == b'.'
-    }
-    
-    pub fn parse(input: &[u8], number_of_symbols: Symbol) -> IResult<&[u8], Alphabet> {
+    /// Creates an alphabet from symbol names in order of their numbers.  The
+    /// first symbol is always epsilon.
+    pub fn new(mut key_table: KeyTable) -> Alphabet {
+        let mut names = FlagNames::new();
+        let mut kinds: Vec<_> = key_table.iter()
+            .map(|s| match names.parse(s) {
+                Some(op) => SymbolKind::FlagDiacritic(op),
+                None => SymbolKind::of_special(s).unwrap_or(SymbolKind::Regular),
+            })
+            .collect();
+        let state_size = names.features.len() as Feature;
+        if let Some(epsilon) = key_table.first_mut() {
+            *epsilon = String::new();
+            kinds[0] = SymbolKind::Epsilon;
+        }
+        let find = |kind| kinds.iter()
+            .position(|&k| k == kind)
+            .map(|k| k as Symbol);
+        let identity = find(SymbolKind::Identity);
+        let unknown = find(SymbolKind::Unknown);
+        Alphabet {
+            key_table,
+            kinds,
+            state_size,
+            identity,
+            unknown,
+        }
+    }
+
+    pub(crate) fn parse(input: &[u8], number_of_symbols: Symbol) -> IResult<&[u8], Alphabet> {
         let mut key_table = Vec::with_capacity(number_of_symbols as usize);
-        let mut state_size = 0u16;
         let input = (0..number_of_symbols)
             .try_fold(input, |input, _| {
                 let (input, s) = terminated(take_till(|b| b == 0), tag(b"\0"))(input)?;
-                if Alphabet::parse_flag_diacritic(s) {
-                    state_size += 1;
-                } else {
-                    key_table.push(unsafe {
-                        String::from_utf8_unchecked(s.to_vec())
-                    });
-                }
+                key_table.push(unsafe {
+                    String::from_utf8_unchecked(s.to_vec())
+                });
                 Ok(input)
             })?;
-        key_table[0] = String::new();
-        Ok((input, Alphabet {
-            key_table,
-            state_size,
-        }))
+        Ok((input, Alphabet::new(key_table)))
     }
 
-    pub fn to_trie(&self, number_of_input_symbols: Symbol) -> Trie<Symbol> {
+    /// Writes the symbol table in the binary format.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        for (k, s) in self.key_table.iter().enumerate() {
+            if k == 0 {
+                out.extend_from_slice(b"@_EPSILON_SYMBOL_@");
+            } else {
+                out.extend_from_slice(s.as_bytes());
+            }
+            out.push(0);
+        }
+    }
+
+    /// Builds a tokenizer for input symbols.  Special symbols and flag
+    /// diacritics are not a part of it.
+    pub(crate) fn to_trie(&self, number_of_input_symbols: Symbol) -> Trie<Symbol> {
         let mut trie = Trie::new(None);
         for k in 1..number_of_input_symbols {
-            trie.insert(&self.key_table[k as usize], k);
+            if self.kind(k) == SymbolKind::Regular {
+                trie.insert(&self.key_table[k as usize], k);
+            }
         }
+        trie.shrink_to_fit();
         trie
     }
 }
@@ -61,7 +159,91 @@ impl Alphabet {
         &self.key_table
     }
 
-    pub fn state_size(&self) -> Symbol {
+    /// Number of distinct features used by flag diacritics.
+    pub fn state_size(&self) -> Feature {
         self.state_size
     }
+
+    /// Returns the kind of a symbol.  Symbols outside of the table are
+    /// considered regular.
+    pub fn kind(&self, symbol: Symbol) -> SymbolKind {
+        self.kinds.get(symbol as usize).copied().unwrap_or(SymbolKind::Regular)
+    }
+
+    pub fn flag_diacritic(&self, symbol: Symbol) -> Option<&FlagDiacriticOperation> {
+        match self.kinds.get(symbol as usize) {
+            Some(SymbolKind::FlagDiacritic(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    pub fn is_flag(&self, symbol: Symbol) -> bool {
+        self.flag_diacritic(symbol).is_some()
+    }
+
+    /// Returns `true` for flag diacritics and special symbols like
+    /// `@_UNKNOWN_SYMBOL_@`.
+    pub fn is_special(&self, symbol: Symbol) -> bool {
+        self.kind(symbol) != SymbolKind::Regular
+    }
+
+    /// Symbols of the transitions which read an input symbol.  Symbols out of
+    /// the key table are unknown, and are read by identity and unknown
+    /// transitions.
+    pub(crate) fn readers(&self, symbol: Symbol) -> [Option<Symbol>; 2] {
+        if (symbol as usize) < self.key_table.len() {
+            [Some(symbol), None]
+        } else {
+            [self.identity, self.unknown]
+        }
+    }
+
+    /// Resolves a transition taken on an input symbol.  Identity transitions
+    /// copy the input symbol to the output.
+    pub(crate) fn resolve(&self, tr: WeightedTransition, symbol: Symbol) -> WeightedTransition {
+        if self.identity.is_some_and(|identity| tr.output() == identity) {
+            WeightedTransition::new(tr.input(), symbol, tr.target(), tr.weight())
+        } else {
+            tr
+        }
+    }
+
+    /// Maps every symbol to the symbol of `other` with the same name, or to
+    /// [`NO_SYMBOL_NUMBER`] if `other` has none.  Epsilon maps to epsilon.
+    pub(crate) fn translation(&self, other: &Alphabet) -> Vec<Symbol> {
+        let numbers: BTreeMap<_, _> = other.key_table.iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, name)| (name.as_str(), i as Symbol))
+            .collect();
+        self.key_table.iter()
+            .enumerate()
+            .map(|(i, name)| match i as Symbol {
+                EPSILON => EPSILON,
+                _ => numbers.get(name.as_str()).copied().unwrap_or(NO_SYMBOL_NUMBER),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_keep_their_numbers() {
+        let input = b"@_EPSILON_SYMBOL_@\0a\0@P.Case.Nom@\0b\0@_UNKNOWN_SYMBOL_@\0";
+        let (rest, alphabet) = Alphabet::parse(input, 5).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(alphabet.key_table()[0], "");
+        assert_eq!(alphabet.key_table()[2], "@P.Case.Nom@");
+        assert_eq!(alphabet.key_table()[3], "b");
+        assert!(alphabet.is_flag(2));
+        assert_eq!(alphabet.kind(4), SymbolKind::Unknown);
+        assert_eq!(alphabet.kind(3), SymbolKind::Regular);
+
+        let trie = alphabet.to_trie(5);
+        assert_eq!(trie.get("b"), (Some(&3), ""));
+        assert_eq!(trie.get("@P.Case.Nom@"), (None, "@P.Case.Nom@"));
+    }
 }
@@ -0,0 +1,187 @@
+use alloc::{string::String, vec::Vec};
+use core::{fmt, ops::Range};
+use super::Weight;
+use super::options::Restoration;
+
+/// Output symbol of an analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalysisSymbol {
+    /// A symbol of one character, which may carry combining marks, such as a
+    /// letter of the lemma or the surface form.
+    Character(String),
+    /// A multicharacter symbol, such as `+N`, `Sg` or `@P.X.Y@`.  Tags of one
+    /// letter cannot be told from letters, and are characters.
+    Tag(String),
+}
+
+impl AnalysisSymbol {
+    /// Classifies a symbol from the key table.  Returns `None` for epsilon.
+    pub fn new(symbol: &str) -> Option<Self> {
+        if symbol.is_empty() {
+            return None;
+        }
+        let mut chars = symbol.chars();
+        let character = chars.next().is_some_and(|first| !is_combining(first))
+            && chars.all(is_combining);
+        let symbol = String::from(symbol);
+        Some(if character {
+            AnalysisSymbol::Character(symbol)
+        } else {
+            AnalysisSymbol::Tag(symbol)
+        })
+    }
+}
+
+/// Whether a character attaches to the one before it, like combining
+/// diacritical marks, variation selectors and the zero width joiner.
+fn is_combining(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+impl fmt::Display for AnalysisSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisSymbol::Character(symbol) | AnalysisSymbol::Tag(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+/// Result of a lookup which keeps the output symbols apart, so tags do not
+/// need to be split out of a string.
+///
+/// It is displayed as the string [`super::Transducer::lookup()`] returns.
+///
+/// ```
+/// use hfstol::{Transducer, compile_att};
+///
+/// let t = compile_att("0\t1\tк\tк\n1\t2\tо\tо\n2\t3\t@0@\t+N\n3\t4\t@0@\t+Sg\t0.5\n4\n").unwrap();
+/// let analysis = &t.lookup_analyses("ко", t.options()).unwrap()[0];
+/// assert_eq!(analysis.lemma(), "ко");
+/// assert_eq!(analysis.tags().collect::<Vec<_>>(), ["+N", "+Sg"]);
+/// assert_eq!(analysis.weight(), 0.5);
+/// assert_eq!(analysis.to_string(), "ко+N+Sg");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    symbols: Vec<AnalysisSymbol>,
+    weight: Weight,
+}
+
+impl Analysis {
+    pub fn new(symbols: Vec<AnalysisSymbol>, weight: Weight) -> Self {
+        Analysis { symbols, weight }
+    }
+
+    pub fn symbols(&self) -> &[AnalysisSymbol] {
+        &self.symbols
+    }
+
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+
+    /// Characters before the first tag.
+    pub fn lemma(&self) -> String {
+        self.symbols.iter()
+            .map_while(|symbol| match symbol {
+                AnalysisSymbol::Character(symbol) => Some(symbol.as_str()),
+                AnalysisSymbol::Tag(_) => None,
+            })
+            .collect()
+    }
+
+    /// All the tags, in order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().filter_map(|symbol| match symbol {
+            AnalysisSymbol::Tag(tag) => Some(tag.as_str()),
+            AnalysisSymbol::Character(_) => None,
+        })
+    }
+
+    /// Changes the case of the characters, leaving the tags as they are.
+    pub(crate) fn restore_case(&mut self, restoration: Restoration) {
+        let mut first = true;
+        for symbol in &mut self.symbols {
+            if let AnalysisSymbol::Character(symbol) = symbol {
+                if first {
+                    first = false;
+                    *symbol = restoration.apply(symbol);
+                } else if restoration == Restoration::Uppercase {
+                    *symbol = symbol.to_uppercase();
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.symbols.iter().try_for_each(|symbol| symbol.fmt(f))
+    }
+}
+
+/// Analyses of a lookup with details of the search, see
+/// [`super::Transducer::lookup_report()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupReport {
+    pub analyses: Vec<(String, Weight)>,
+    /// Number of paths cut at input-epsilon cycles, see
+    /// [`super::LookupOptions::epsilon_cycles`].  Analyses those paths would
+    /// lead to are missing.
+    pub cycles_cut: usize,
+}
+
+/// Input and output symbols of a transition on an analysis path, see
+/// [`super::Transducer::lookup_aligned()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolPair {
+    /// Input symbol, empty for epsilon.
+    pub input: String,
+    /// Output symbol, empty for epsilon.
+    pub output: String,
+    /// Byte range of the input symbol in the looked-up string.  For epsilon
+    /// it is empty, at the position of the next input symbol.
+    pub span: Range<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn restore_case() {
+        let symbols = ["s", "t", "r", "a", "ß", "e", "+N", "+Sg"].iter()
+            .filter_map(|&s| AnalysisSymbol::new(s))
+            .collect();
+        let mut analysis = Analysis::new(symbols, 0.0);
+        assert_eq!(analysis.tags().collect::<Vec<_>>(), ["+N", "+Sg"]);
+        let mut capitalized = analysis.clone();
+        capitalized.restore_case(Restoration::Capitalize);
+        assert_eq!(capitalized.to_string(), "Straße+N+Sg");
+        analysis.restore_case(Restoration::Uppercase);
+        assert_eq!(analysis.lemma(), "STRASSE");
+        assert_eq!(analysis.to_string(), "STRASSE+N+Sg");
+    }
+
+    #[test]
+    fn multicharacter_tags() {
+        let symbols: Vec<_> = ["c", "a", "f", "e\u{301}", "+", "Sg", "Nom", "+Pl", "@P.X.Y@"].iter()
+            .filter_map(|&s| AnalysisSymbol::new(s))
+            .collect();
+        let analysis = Analysis::new(symbols, 0.0);
+        assert_eq!(analysis.lemma(), "cafe\u{301}+");
+        assert_eq!(analysis.tags().collect::<Vec<_>>(), ["Sg", "Nom", "+Pl", "@P.X.Y@"]);
+        assert_eq!(AnalysisSymbol::new("\u{301}e"), Some(AnalysisSymbol::Tag("\u{301}e".to_string())));
+        assert_eq!(AnalysisSymbol::new(""), None);
+    }
+}
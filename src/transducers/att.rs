@@ -0,0 +1,158 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    vec::Vec,
+};
+use core::fmt::Write;
+use super::{
+    Error,
+    Weight,
+    basic::{BasicTransducer, BasicTransition, StateNumber},
+};
+
+/// Converts a symbol from its AT&T spelling.
+fn unescape(symbol: &str) -> &str {
+    match symbol {
+        "@0@" | "@_EPSILON_SYMBOL_@" => "@_EPSILON_SYMBOL_@",
+        "@_SPACE_@" => " ",
+        "@_TAB_@" => "\t",
+        _ => symbol,
+    }
+}
+
+/// Converts a symbol to its AT&T spelling.
+fn escape(symbol: &str) -> &str {
+    match symbol {
+        "" | "@_EPSILON_SYMBOL_@" => "@0@",
+        " " => "@_SPACE_@",
+        "\t" => "@_TAB_@",
+        _ => symbol,
+    }
+}
+
+impl BasicTransducer {
+    /// Reads a transducer in AT&T tab-separated text format.
+    ///
+    /// Transition lines have form `src dst in out [weight]`, and final state
+    /// lines `state [weight]`.  The source state of the first line is the
+    /// initial state.  Epsilon is written as `@0@` or `@_EPSILON_SYMBOL_@`.
+    /// Only the first transducer is read if there are several separated with
+    /// `--`.
+    ///
+    /// Returns [`Error::AttParsingError`] with the number of the first
+    /// malformed line.
+    pub fn from_att(text: &str) -> Result<BasicTransducer, Error> {
+        let mut res = BasicTransducer::new();
+        let mut states: BTreeMap<&str, StateNumber> = BTreeMap::new();
+        let mut state = |res: &mut BasicTransducer, name| match states.get(name) {
+            Some(&n) => n,
+            None => {
+                // The first state seen is the initial one, which always exists
+                let n = if states.is_empty() { 0 } else { res.add_state() };
+                states.insert(name, n);
+                n
+            },
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let error = || Error::AttParsingError(number + 1);
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            if line == "--" {
+                break;
+            }
+            let fields: Vec<&str> = if line.contains('\t') {
+                line.split('\t').collect()
+            } else {
+                line.split_whitespace().collect()
+            };
+            let weight = |field: Option<&&str>| match field {
+                Some(w) => w.parse::<Weight>().map_err(|_| error()),
+                None => Ok(0.0),
+            };
+            match fields.len() {
+                1 | 2 => {
+                    let weight = weight(fields.get(1))?;
+                    let s = state(&mut res, fields[0]);
+                    res.set_final(s, Some(weight));
+                },
+                4 | 5 => {
+                    let weight = weight(fields.get(4))?;
+                    let source = state(&mut res, fields[0]);
+                    let target = state(&mut res, fields[1]);
+                    let input = res.add_symbol(unescape(fields[2]))?;
+                    let output = res.add_symbol(unescape(fields[3]))?;
+                    res.add_transition(source, BasicTransition {
+                        input,
+                        output,
+                        target,
+                        weight,
+                    });
+                },
+                _ => return Err(error()),
+            }
+        }
+        Ok(res)
+    }
+
+    /// Writes the transducer in AT&T text format.
+    ///
+    /// Transitions are listed state by state, each state followed by its final
+    /// line if it is final.  Weights are printed only if `weighted` is set.
+    pub fn to_att(&self, weighted: bool) -> String {
+        let mut res = String::new();
+        let symbol = |s: u16| escape(&self.symbols()[s as usize]);
+        for (n, state) in self.states().iter().enumerate() {
+            for tr in &state.transitions {
+                let _ = write!(res, "{}\t{}\t{}\t{}", n, tr.target, symbol(tr.input), symbol(tr.output));
+                if weighted {
+                    let _ = write!(res, "\t{:.6}", tr.weight);
+                }
+                res.push('\n');
+            }
+            if let Some(weight) = state.final_weight {
+                let _ = write!(res, "{}", n);
+                if weighted {
+                    let _ = write!(res, "\t{:.6}", weight);
+                }
+                res.push('\n');
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_and_states() {
+        let t = BasicTransducer::from_att("5\t3\t@_SPACE_@\t@0@\t0.5\n3\n").unwrap();
+        assert_eq!(t.states().len(), 2);
+        let tr = t.states()[0].transitions[0];
+        assert_eq!(t.symbols()[tr.input as usize], " ");
+        assert_eq!(tr.output, 0);
+        assert_eq!(tr.target, 1);
+        assert_eq!(t.states()[1].final_weight, Some(0.0));
+    }
+
+    #[test]
+    fn export() {
+        let text = "0\t1\ta\t@_SPACE_@\t0.500000\n1\t1\t@0@\tb\t0.000000\n1\t1.000000\n";
+        let t = BasicTransducer::from_att(text).unwrap().compile().unwrap();
+        let exported = BasicTransducer::from_transducer(&t).unwrap();
+        assert_eq!(exported.to_att(true), text);
+        assert_eq!(exported.to_att(false), "0\t1\ta\t@_SPACE_@\n1\t1\t@0@\tb\n1\n");
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(matches!(
+            BasicTransducer::from_att("0\t1\ta\ta\n1\t2\tb\n"),
+            Err(Error::AttParsingError(2)),
+        ));
+    }
+}
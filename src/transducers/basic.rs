@@ -0,0 +1,598 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    vec::Vec,
+    vec,
+};
+use super::{
+    Transducer,
+    Error,
+    Header, Alphabet, SymbolKind,
+    TransitionIndex, WeightedTransition,
+    WeightedTransducer,
+    Symbol, TransitionTableIndex, Weight,
+    NO_SYMBOL_NUMBER, NO_TABLE_INDEX, TRANSITION_TARGET_TABLE_START, EPSILON,
+};
+
+/// Number of a state in a [`BasicTransducer`].
+pub type StateNumber = u32;
+
+/// Transition of a [`BasicTransducer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasicTransition {
+    pub input: Symbol,
+    pub output: Symbol,
+    pub target: StateNumber,
+    pub weight: Weight,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BasicState {
+    pub transitions: Vec<BasicTransition>,
+    pub final_weight: Option<Weight>,
+}
+
+/// Transducer as a plain graph of states and transitions.
+///
+/// Unlike the packed transition tables of an optimized-lookup transducer, it
+/// is easy to build and change.  Use [`BasicTransducer::compile()`] to get a
+/// transducer suitable for lookup.  State 0 is the initial state, symbol 0 is
+/// epsilon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicTransducer {
+    symbols: Vec<String>,
+    symbol_numbers: BTreeMap<String, Symbol>,
+    states: Vec<BasicState>,
+}
+
+impl Default for BasicTransducer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BasicTransducer {
+    /// Creates a transducer with only the initial state.
+    pub fn new() -> Self {
+        let epsilon = String::from("@_EPSILON_SYMBOL_@");
+        BasicTransducer {
+            symbol_numbers: BTreeMap::from([(epsilon.clone(), EPSILON)]),
+            symbols: vec![epsilon],
+            states: vec![BasicState::default()],
+        }
+    }
+
+    /// Creates a transducer with only the initial state and the given symbols,
+    /// numbered in order.  The first symbol must be epsilon.
+    pub fn with_symbols(symbols: Vec<String>) -> Self {
+        let mut symbol_numbers = BTreeMap::new();
+        for (n, s) in symbols.iter().enumerate().rev() {
+            symbol_numbers.insert(s.clone(), n as Symbol);
+        }
+        BasicTransducer {
+            symbols,
+            symbol_numbers,
+            states: vec![BasicState::default()],
+        }
+    }
+
+    /// Unpacks the transition tables of a transducer into a graph.
+    ///
+    /// Symbols keep their numbers.  States are numbered in breadth-first
+    /// order from the initial state.  Only states reachable by lookup are
+    /// visited.
+    ///
+    /// Returns [`Error::UnsupportedTransducerError`] if the transducer is not
+    /// backed by transition tables.
+    pub fn from_transducer<T: Transducer + ?Sized>(transducer: &T) -> Result<Self, Error> {
+        let tables = transducer.tables().ok_or(Error::UnsupportedTransducerError)?;
+        let alphabet = transducer.alphabet();
+        let mut symbols = alphabet.key_table().clone();
+        if let Some(epsilon) = symbols.first_mut() {
+            *epsilon = String::from("@_EPSILON_SYMBOL_@");
+        }
+        let number_of_input_symbols = transducer.header()
+            .map(Header::number_of_input_symbols)
+            .unwrap_or(symbols.len() as Symbol);
+        let class = |input: Symbol| if alphabet.is_flag(input) { EPSILON } else { input };
+
+        let mut res = BasicTransducer::with_symbols(symbols);
+        let mut numbers = BTreeMap::from([(0, 0)]);
+        let mut queue = VecDeque::from([0]);
+        while let Some(id) = queue.pop_front() {
+            let source = numbers[&id];
+
+            // Runs of transitions in the target table, with their input class
+            let mut runs = Vec::new();
+            let final_weight = if id >= TRANSITION_TARGET_TABLE_START {
+                let t = id - TRANSITION_TARGET_TABLE_START;
+                if let Some(tr) = tables.transition(t + 1).filter(|tr| tr.input() != NO_SYMBOL_NUMBER) {
+                    runs.push((t + 1, class(tr.input())));
+                }
+                tables.transition(t)
+                    .filter(|tr| tr.is_final())
+                    .map(|tr| tr.weight())
+            } else {
+                for c in (0..number_of_input_symbols).filter(|&c| !alphabet.is_flag(c)) {
+                    if let Some(tr) = tables.transition_index(id + c as TransitionTableIndex + 1)
+                        .filter(|tr| tr.symbol() == c && tr.target() >= TRANSITION_TARGET_TABLE_START)
+                    {
+                        runs.push((tr.target() - TRANSITION_TARGET_TABLE_START, c));
+                    }
+                }
+                tables.transition_index(id)
+                    .filter(|tr| tr.is_final())
+                    .map(|tr| tables.index_weight(&tr))
+            };
+            res.set_final(source, final_weight);
+
+            for (mut i, c) in runs {
+                while let Some(tr) = tables.transition(i).filter(|tr| class(tr.input()) == c) {
+                    let target = *numbers.entry(tr.target()).or_insert_with(|| {
+                        queue.push_back(tr.target());
+                        res.add_state()
+                    });
+                    res.add_transition(source, BasicTransition {
+                        input: tr.input(),
+                        output: tr.output(),
+                        target,
+                        weight: tr.weight(),
+                    });
+                    i += 1;
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the number of a symbol, adding it if it is not known yet.
+    pub fn add_symbol(&mut self, symbol: &str) -> Result<Symbol, Error> {
+        if let Some(&number) = self.symbol_numbers.get(symbol) {
+            return Ok(number);
+        }
+        let number = Symbol::try_from(self.symbols.len())
+            .ok()
+            .filter(|&n| n != NO_SYMBOL_NUMBER)
+            .ok_or(Error::UnsupportedTransducerError)?;
+        self.symbols.push(String::from(symbol));
+        self.symbol_numbers.insert(String::from(symbol), number);
+        Ok(number)
+    }
+
+    pub fn symbol_number(&self, symbol: &str) -> Option<Symbol> {
+        self.symbol_numbers.get(symbol).copied()
+    }
+
+    /// Symbol names in order of their numbers.
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn add_state(&mut self) -> StateNumber {
+        self.states.push(BasicState::default());
+        (self.states.len() - 1) as StateNumber
+    }
+
+    pub fn states(&self) -> &[BasicState] {
+        &self.states
+    }
+
+    pub fn state(&self, state: StateNumber) -> Option<&BasicState> {
+        self.states.get(state as usize)
+    }
+
+    /// Adds a transition from `source`.  Both states must exist.
+    pub fn add_transition(&mut self, source: StateNumber, transition: BasicTransition) {
+        self.states[source as usize].transitions.push(transition);
+    }
+
+    pub fn set_final(&mut self, state: StateNumber, weight: Option<Weight>) {
+        self.states[state as usize].final_weight = weight;
+    }
+
+    /// Swaps input and output symbols of all transitions.
+    pub fn invert(&mut self) {
+        for tr in self.states.iter_mut().flat_map(|s| &mut s.transitions) {
+            core::mem::swap(&mut tr.input, &mut tr.output);
+        }
+    }
+
+    /// Packs the transducer into transition index and target tables, the way
+    /// `hfst-fst2fst -w` does.
+    ///
+    /// States having transitions with different input symbols, as well as the
+    /// initial state, get a block in the index table.  Others live in the
+    /// target table only.  Flag diacritics are indexed with epsilon.
+    pub fn compile(&self) -> Result<WeightedTransducer, Error> {
+        // Input symbols come first, so they could be indexed
+        let mut is_input = vec![false; self.symbols.len()];
+        is_input[EPSILON as usize] = true;
+        for tr in self.states.iter().flat_map(|s| &s.transitions) {
+            is_input[tr.input as usize] = true;
+        }
+        let mut order: Vec<Symbol> = (0..self.symbols.len() as Symbol).collect();
+        order[1..].sort_by_key(|&s| (!is_input[s as usize], &self.symbols[s as usize]));
+        let mut renumber = vec![0; self.symbols.len()];
+        for (new, &old) in order.iter().enumerate() {
+            renumber[old as usize] = new as Symbol;
+        }
+        let number_of_input_symbols = is_input.iter().filter(|&&i| i).count() as Symbol;
+        let alphabet = Alphabet::new(
+            order.iter().map(|&s| self.symbols[s as usize].clone()).collect()
+        );
+        // Flags are traversed along with epsilons, so they share its slot
+        let class = |input: Symbol| match alphabet.kind(input) {
+            SymbolKind::FlagDiacritic(_) => EPSILON,
+            _ => input,
+        };
+
+        // Transitions of every state, renumbered and grouped by input class
+        let states: Vec<Vec<BasicTransition>> = self.states.iter()
+            .map(|state| {
+                let mut transitions: Vec<_> = state.transitions.iter()
+                    .map(|tr| BasicTransition {
+                        input: renumber[tr.input as usize],
+                        output: renumber[tr.output as usize],
+                        ..*tr
+                    })
+                    .collect();
+                transitions.sort_by_key(|tr| (class(tr.input), tr.input));
+                transitions
+            })
+            .collect();
+        let indexed: Vec<bool> = states.iter().enumerate()
+            .map(|(n, transitions)| n == 0 || transitions.windows(2)
+                .any(|pair| class(pair[0].input) != class(pair[1].input)))
+            .collect();
+
+        // Every state starts its block in the target table with an entry,
+        // which also separates it from transitions of the previous state
+        let mut target_positions = Vec::with_capacity(states.len());
+        let mut size_of_transition_target_table: usize = 0;
+        for transitions in &states {
+            target_positions.push(size_of_transition_target_table);
+            size_of_transition_target_table += transitions.len() + 1;
+        }
+
+        let mut index_positions = vec![NO_TABLE_INDEX; states.len()];
+        let mut packer = IndexPacker::default();
+        for (n, transitions) in states.iter().enumerate().filter(|&(n, _)| indexed[n]) {
+            // The first slot of a block holds the final weight
+            let mut offsets: Vec<usize> = core::iter::once(0)
+                .chain(transitions.iter().map(|tr| class(tr.input) as usize + 1))
+                .collect();
+            offsets.dedup();
+            index_positions[n] = packer.place(&offsets) as TransitionTableIndex;
+        }
+        let size_of_transition_index_table = match index_positions.iter().filter(|&&p| p != NO_TABLE_INDEX).max() {
+            Some(&max) => max as usize + number_of_input_symbols as usize + 1,
+            None => 0,
+        };
+        if size_of_transition_index_table >= TRANSITION_TARGET_TABLE_START as usize
+            || size_of_transition_target_table >= TRANSITION_TARGET_TABLE_START as usize
+        {
+            return Err(Error::UnsupportedTransducerError);
+        }
+
+        let state_id = |n: StateNumber| match index_positions[n as usize] {
+            NO_TABLE_INDEX => TRANSITION_TARGET_TABLE_START + target_positions[n as usize] as TransitionTableIndex,
+            p => p,
+        };
+
+        let mut index = vec![
+            TransitionIndex::new(NO_SYMBOL_NUMBER, NO_TABLE_INDEX);
+            size_of_transition_index_table
+        ];
+        let mut table = Vec::with_capacity(size_of_transition_target_table);
+        let mut number_of_transitions = 0;
+        let mut has_input_epsilon_transitions = false;
+        let mut has_epsilon_epsilon_transitions = false;
+        for (n, transitions) in states.iter().enumerate() {
+            let final_weight = self.states[n].final_weight;
+            let is_indexed = indexed[n];
+            table.push(match final_weight {
+                Some(weight) if !is_indexed => WeightedTransition::new_final(weight),
+                _ => WeightedTransition::default(),
+            });
+            if is_indexed {
+                let base = index_positions[n] as usize;
+                if let Some(weight) = final_weight {
+                    index[base] = TransitionIndex::new_final(weight);
+                }
+            }
+            for (i, tr) in transitions.iter().enumerate() {
+                let c = class(tr.input);
+                if is_indexed && (i == 0 || class(transitions[i-1].input) != c) {
+                    index[index_positions[n] as usize + c as usize + 1] = TransitionIndex::new(
+                        c,
+                        TRANSITION_TARGET_TABLE_START + table.len() as TransitionTableIndex,
+                    );
+                }
+                table.push(WeightedTransition::new(
+                    tr.input, tr.output, state_id(tr.target), tr.weight,
+                ));
+                number_of_transitions += 1;
+                has_input_epsilon_transitions |= tr.input == EPSILON;
+                has_epsilon_epsilon_transitions |= tr.input == EPSILON && tr.output == EPSILON;
+            }
+        }
+
+        let mut header = Header::new(
+            number_of_input_symbols,
+            alphabet.key_table().len() as Symbol,
+            size_of_transition_index_table as TransitionTableIndex,
+            size_of_transition_target_table as TransitionTableIndex,
+            states.len() as u32,
+            number_of_transitions,
+            true,
+        );
+        header.set_epsilon_properties(
+            has_epsilon_epsilon_transitions,
+            has_input_epsilon_transitions,
+        );
+        let input_epsilon = |tr: &BasicTransition| class(tr.input) == EPSILON;
+        header.set_cycle_properties(
+            has_cycle(&states, input_epsilon),
+            has_cycle(&states, |tr| input_epsilon(tr) && tr.weight == 0.0),
+        );
+        Ok(WeightedTransducer::new(header, alphabet, index, table))
+    }
+}
+
+/// Number of blocks which may miss a free slot of the index table before it
+/// is given up as the start of a block.
+const MAX_MISSES: u8 = 32;
+
+/// Places blocks of the transition index table first-fit.
+///
+/// Free slots which blocks keep missing are given up as block starts, and
+/// skipped along with the used ones, so packing takes time linear in the
+/// size of the table.
+#[derive(Default)]
+struct IndexPacker {
+    used: Vec<bool>,
+    /// Slot at or after each slot where a block may start.  Slots pointing
+    /// to themselves are free and not given up, like the ones past the end.
+    next: Vec<usize>,
+    /// Number of blocks which did not fit at each slot.
+    misses: Vec<u8>,
+    /// Number of slots tried as block starts.
+    probes: usize,
+}
+
+impl IndexPacker {
+    /// Places a block with slots at the given offsets from its start, and
+    /// returns the start.
+    fn place(&mut self, offsets: &[usize]) -> usize {
+        let mut base = self.find(0);
+        loop {
+            self.probes += 1;
+            if offsets.iter().all(|&offset| !self.used.get(base + offset).copied().unwrap_or(false)) {
+                break;
+            }
+            self.misses[base] += 1;
+            if self.misses[base] >= MAX_MISSES {
+                self.next[base] = base + 1;
+            }
+            base = self.find(base + 1);
+        }
+        for &offset in offsets {
+            let slot = base + offset;
+            self.grow(slot + 1);
+            self.used[slot] = true;
+            self.next[slot] = slot + 1;
+        }
+        base
+    }
+
+    /// Returns the first slot at or after `slot` where a block may start.
+    fn find(&mut self, slot: usize) -> usize {
+        let mut found = slot;
+        while found < self.next.len() && self.next[found] != found {
+            found = self.next[found];
+        }
+        // Skipped slots point past each other from now on
+        let mut skipped = slot;
+        while skipped < found {
+            skipped = core::mem::replace(&mut self.next[skipped], found);
+        }
+        self.grow(found + 1);
+        found
+    }
+
+    fn grow(&mut self, len: usize) {
+        while self.next.len() < len {
+            self.next.push(self.next.len());
+        }
+        self.used.resize(len.max(self.used.len()), false);
+        self.misses.resize(len.max(self.misses.len()), 0);
+    }
+}
+
+/// Checks whether the graph has a cycle made of transitions accepted by
+/// `follow`.
+fn has_cycle<F: Fn(&BasicTransition) -> bool>(states: &[Vec<BasicTransition>], follow: F) -> bool {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        OnPath,
+        Done,
+    }
+    let mut marks = vec![Mark::New; states.len()];
+    for start in 0..states.len() {
+        if marks[start] != Mark::New {
+            continue;
+        }
+        marks[start] = Mark::OnPath;
+        // States on the current path, with the next transition to follow
+        let mut stack = vec![(start, 0)];
+        while let Some((state, next)) = stack.last_mut() {
+            let state = *state;
+            match states[state][*next..].iter().position(&follow) {
+                Some(offset) => {
+                    let tr = &states[state][*next + offset];
+                    *next += offset + 1;
+                    let target = tr.target as usize;
+                    match marks[target] {
+                        Mark::OnPath => return true,
+                        Mark::New => {
+                            marks[target] = Mark::OnPath;
+                            stack.push((target, 0));
+                        },
+                        Mark::Done => {},
+                    }
+                },
+                None => {
+                    marks[state] = Mark::Done;
+                    stack.pop();
+                },
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::{String, ToString}, vec, vec::Vec};
+    use super::{BasicTransducer, BasicTransition, IndexPacker};
+    use crate::transducers::{Symbol, Weight, EPSILON};
+    use crate::transducers::{
+        Transducer, LookupOptions,
+        compile_att, read_transducer, write_transducer, WriteOptions,
+    };
+
+    #[test]
+    fn ambiguous() {
+        let t = compile_att("\
+            0\t1\ta\ta\n\
+            0\t2\ta\tb\t1\n\
+            0\t3\tb\tc\n\
+            1\n\
+            2\t0.5\n\
+            3\t4\t@0@\td\n\
+            4\n\
+        ").unwrap();
+        let mut analyses = t.lookup("a").unwrap();
+        analyses.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(analyses, vec![("a".to_string(), 0.0), ("b".to_string(), 1.5)]);
+        assert_eq!(t.lookup("b").unwrap(), vec![("cd".to_string(), 0.0)]);
+        assert_eq!(t.lookup("ab").unwrap(), vec![]);
+
+        let bytes = write_transducer(&t, &WriteOptions::default()).unwrap();
+        let read = read_transducer(&bytes).unwrap();
+        assert_eq!(read.lookup("b").unwrap(), t.lookup("b").unwrap());
+    }
+
+    #[test]
+    fn invert() {
+        let t = compile_att("\
+            0\t1\tc\tc\n\
+            1\t2\ta\ta\n\
+            2\t3\tt\tt\n\
+            3\t4\t@0@\t+N\n\
+            4\t5\t@0@\t+Nom\t0.5\n\
+            5\n\
+        ").unwrap();
+        assert_eq!(t.lookup("cat").unwrap(), vec![("cat+N+Nom".to_string(), 0.5)]);
+        let generator = t.invert().unwrap();
+        assert_eq!(generator.tokenize("cat+N+Nom").unwrap().len(), 5);
+        assert_eq!(generator.lookup("cat+N+Nom").unwrap(), vec![("cat".to_string(), 0.5)]);
+        assert_eq!(generator.lookup("cat+Nom+N").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn invert_large() {
+        // Analyser of 20000 words spelled with five letters, as a trie
+        let mut basic = BasicTransducer::new();
+        let letters: Vec<Symbol> = ["a", "b", "c", "d", "e"].iter()
+            .map(|letter| basic.add_symbol(letter).unwrap())
+            .collect();
+        let tag = basic.add_symbol("+N").unwrap();
+        let mut children = BTreeMap::new();
+        let mut words = Vec::new();
+        for i in 0..20_000u32 {
+            let mut state = 0;
+            let mut word = String::new();
+            for k in 0..7 {
+                let letter = letters[(i / 5u32.pow(k)) as usize % 5];
+                word.push_str(&basic.symbols()[letter as usize]);
+                state = *children.entry((state, letter)).or_insert_with(|| {
+                    let target = basic.add_state();
+                    basic.add_transition(state, BasicTransition {
+                        input: letter,
+                        output: letter,
+                        target,
+                        weight: 0.0,
+                    });
+                    target
+                });
+            }
+            let end = basic.add_state();
+            basic.add_transition(state, BasicTransition {
+                input: EPSILON,
+                output: tag,
+                target: end,
+                weight: i as Weight,
+            });
+            basic.set_final(end, Some(0.0));
+            words.push((word, i as Weight));
+        }
+        let generator = basic.compile().unwrap().invert().unwrap();
+        for (word, weight) in words.iter().step_by(997) {
+            let analysis = alloc::format!("{word}+N");
+            assert_eq!(generator.lookup(&analysis).unwrap(), vec![(word.clone(), *weight)]);
+        }
+    }
+
+    #[test]
+    fn cycle() {
+        let t = compile_att("0\t0\ta\tb\n0\n").unwrap();
+        assert_eq!(t.lookup("aaa").unwrap(), vec![("bbb".to_string(), 0.0)]);
+        assert_eq!(t.lookup("").unwrap(), vec![("".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn packing_scales() {
+        // Blocks of up to three pseudo-random classes leave holes which few
+        // blocks fit
+        let probes = |blocks: usize| {
+            let mut packer = IndexPacker::default();
+            let mut seed: u32 = 1;
+            for _ in 0..blocks {
+                let mut offsets = vec![0];
+                for _ in 0..3 {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    offsets.push((seed >> 16) as usize % 100 + 1);
+                }
+                offsets.sort_unstable();
+                offsets.dedup();
+                packer.place(&offsets);
+            }
+            packer.probes
+        };
+        let (small, large) = (probes(10_000), probes(40_000));
+        assert!(large < 5 * small, "{small} probes for 10k blocks, {large} for 40k");
+    }
+
+    #[test]
+    fn flag_diacritics() {
+        let t = compile_att("\
+            0\t1\t@P.X.A@\t@P.X.A@\n\
+            0\t1\t@P.X.B@\t@P.X.B@\n\
+            1\t2\tx\tx\n\
+            2\t3\t@R.X.A@\t@R.X.A@\n\
+            3\n\
+        ").unwrap();
+        assert_eq!(t.lookup("x").unwrap(), vec![("x".to_string(), 0.0)]);
+        let options = LookupOptions {
+            show_flags: true,
+            ..LookupOptions::default()
+        };
+        assert_eq!(
+            t.lookup_with("x", &options).unwrap(),
+            vec![("@P.X.A@x@R.X.A@".to_string(), 0.0)],
+        );
+    }
+}
+
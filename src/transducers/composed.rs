@@ -0,0 +1,419 @@
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use super::{
+    Transducer,
+    Error,
+    Alphabet, SymbolKind,
+    LookupOptions, Trie,
+    TransitionTableIndex,
+    Symbol, Weight, EncodedAnalyses,
+    transitions::{TransitionTables, matching_transitions, reading_transitions},
+    lookup::{Graph, Move, Search},
+    flags::FlagState,
+    EPSILON, NO_SYMBOL_NUMBER,
+};
+
+/// Composition of two transducers, computed at lookup time.
+///
+/// Input is looked up in the first transducer, and its output symbols are
+/// read by the second one, matched by name.  Weights of both paths are summed.
+/// Tokenization uses the input symbols of the first transducer, and analyses
+/// are decoded with the symbols of the second.
+///
+/// Output symbols of the first transducer which the second one lacks are
+/// unknown to the second one, so its identity and unknown transitions read
+/// them.
+///
+/// ```
+/// use hfstol::{Transducer, compile_att, transducers::ComposedTransducer};
+///
+/// let normaliser = compile_att("0\t1\tö\to\t1\n0\t1\to\to\n1\n").unwrap();
+/// let analyser = compile_att("0\t1\to\to\n1\t2\t@0@\t+Interj\t0.5\n2\n").unwrap();
+/// let t = ComposedTransducer::new(normaliser, analyser).unwrap();
+/// assert_eq!(t.lookup("ö").unwrap(), vec![("o+Interj".to_string(), 1.5)]);
+/// ```
+pub struct ComposedTransducer<A, B> {
+    first: A,
+    second: B,
+    /// Input symbol of the second transducer for each symbol of the first.
+    translation: Vec<Symbol>,
+    /// Symbols of the second transducer, followed by the symbols of the
+    /// first one it lacks.
+    alphabet: Alphabet,
+    options: LookupOptions,
+}
+
+impl<A: Transducer, B: Transducer> ComposedTransducer<A, B> {
+    /// Composes two transducers.
+    ///
+    /// Both must be backed by transition tables, otherwise
+    /// [`Error::UnsupportedTransducerError`] is returned.
+    pub fn new(first: A, second: B) -> Result<Self, Error> {
+        if first.tables().is_none() || second.tables().is_none() {
+            return Err(Error::UnsupportedTransducerError);
+        }
+        let mut translation = first.alphabet().translation(second.alphabet());
+        // Symbols missing from the second transducer get numbers past its
+        // key table, like unknown symbols of the input
+        let mut key_table = second.key_table().clone();
+        for (symbol, translated) in translation.iter_mut().enumerate() {
+            if *translated == NO_SYMBOL_NUMBER
+                && first.alphabet().kind(symbol as Symbol) == SymbolKind::Regular
+            {
+                *translated = key_table.len() as Symbol;
+                key_table.push(first.key_table()[symbol].clone());
+            }
+        }
+        Ok(ComposedTransducer {
+            first,
+            second,
+            translation,
+            alphabet: Alphabet::new(key_table),
+            options: LookupOptions::default(),
+        })
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    /// Returns the composed transducers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// Pair of states on a path through both transducers.
+#[derive(Clone, Copy)]
+struct Pair {
+    first: TransitionTableIndex,
+    second: TransitionTableIndex,
+    /// Whether the last step moved only the first transducer.  Then the
+    /// second one may not move alone before both move together, so that
+    /// every path is found once.
+    first_alone: bool,
+}
+
+/// Pairs are equal if their states are, however they were reached.
+impl PartialEq for Pair {
+    fn eq(&self, other: &Self) -> bool {
+        (self.first, self.second) == (other.first, other.second)
+    }
+}
+
+/// Transitions of both transducers, taken at once.
+struct Composition<'a> {
+    first: &'a dyn TransitionTables,
+    second: &'a dyn TransitionTables,
+    first_alphabet: &'a Alphabet,
+    second_alphabet: &'a Alphabet,
+    translation: &'a [Symbol],
+    unknown_symbols_start: Symbol,
+    show_flags: bool,
+}
+
+impl<A: Transducer, B: Transducer> ComposedTransducer<A, B> {
+    fn search<'a>(&'a self, options: &'a LookupOptions) -> Result<Search<'a, Composition<'a>>, Error> {
+        // A cycle of the composition makes a cycle in one of the transducers
+        let check_cycles = [self.first.header(), self.second.header()].iter()
+            .any(|header| !header.is_some_and(|h| {
+                !h.has_input_epsilon_cycles() && !h.has_unweighted_input_epsilon_cycles()
+            }));
+        let composition = Composition {
+            first: self.first.tables().ok_or(Error::UnsupportedTransducerError)?,
+            second: self.second.tables().ok_or(Error::UnsupportedTransducerError)?,
+            first_alphabet: self.first.alphabet(),
+            second_alphabet: self.second.alphabet(),
+            translation: &self.translation,
+            unknown_symbols_start: self.unknown_symbols_start(),
+            show_flags: options.show_flags,
+        };
+        Ok(Search::new(composition, options).check_cycles(check_cycles))
+    }
+}
+
+impl Graph for Composition<'_> {
+    type State = Pair;
+    type Flags = (FlagState, FlagState);
+    type Output = Symbol;
+
+    fn start(&self) -> (Self::State, Self::Flags) {
+        let pair = Pair {
+            first: 0,
+            second: 0,
+            first_alone: false,
+        };
+        let flags = (
+            FlagState::new(self.first_alphabet.state_size()),
+            FlagState::new(self.second_alphabet.state_size()),
+        );
+        (pair, flags)
+    }
+
+    fn final_weight(&self, pair: Self::State) -> Option<Weight> {
+        let first = self.first.final_weight(pair.first)?;
+        let second = self.second.final_weight(pair.second)?;
+        Some(first + second)
+    }
+
+    fn moves(
+        &self,
+        pair: Self::State,
+        (first_flags, second_flags): &Self::Flags,
+        symbol: Option<Symbol>,
+        f: &mut dyn FnMut(Move<Self>),
+    ) {
+        let (first, second) = (self.first, self.second);
+        let (first_alphabet, second_alphabet) = (self.first_alphabet, self.second_alphabet);
+        let shown = |symbol| self.show_flags || !second_alphabet.is_flag(symbol);
+
+        if !pair.first_alone {
+            for tr in matching_transitions(second, second_alphabet, pair.second, EPSILON) {
+                let flags = match second_alphabet.flag_diacritic(tr.input()) {
+                    Some(op) => {
+                        let mut flags = second_flags.clone();
+                        if !flags.apply(op) {
+                            continue;
+                        }
+                        Some((first_flags.clone(), flags))
+                    },
+                    None => None,
+                };
+                f(Move {
+                    weight: tr.weight(),
+                    consumed: false,
+                    target: Pair {
+                        second: tr.target(),
+                        first_alone: false,
+                        ..pair
+                    },
+                    flags,
+                    output: shown(tr.output()).then_some(tr.output()),
+                });
+            }
+        }
+
+        let epsilons = matching_transitions(first, first_alphabet, pair.first, EPSILON)
+            .map(|tr| (tr, false));
+        let consumed = symbol
+            .map(|symbol| reading_transitions(first, first_alphabet, pair.first, symbol))
+            .into_iter()
+            .flatten()
+            .map(|tr| (tr, true));
+        for (tr, consumed) in epsilons.chain(consumed) {
+            let flags = match first_alphabet.flag_diacritic(tr.input()) {
+                Some(op) => {
+                    let mut flags = first_flags.clone();
+                    if !flags.apply(op) {
+                        continue;
+                    }
+                    Some((flags, second_flags.clone()))
+                },
+                None => None,
+            };
+            let symbol = tr.output();
+            if symbol == EPSILON || first_alphabet.is_flag(symbol) {
+                f(Move {
+                    weight: tr.weight(),
+                    consumed,
+                    target: Pair {
+                        first: tr.target(),
+                        first_alone: true,
+                        ..pair
+                    },
+                    flags,
+                    output: (symbol == EPSILON).then_some(EPSILON),
+                });
+                continue;
+            }
+            // Outputs of the first transducer are read by the second.
+            // Unknown symbols copied by identity transitions are out of
+            // both alphabets, and stay unknown.
+            let symbol = match self.translation.get(symbol as usize) {
+                Some(&symbol) => symbol,
+                None if symbol >= self.unknown_symbols_start => symbol,
+                None => NO_SYMBOL_NUMBER,
+            };
+            if symbol == NO_SYMBOL_NUMBER {
+                continue;
+            }
+            for tr2 in reading_transitions(second, second_alphabet, pair.second, symbol) {
+                f(Move {
+                    weight: tr.weight() + tr2.weight(),
+                    consumed,
+                    target: Pair {
+                        first: tr.target(),
+                        second: tr2.target(),
+                        first_alone: false,
+                    },
+                    flags: flags.clone(),
+                    output: shown(tr2.output()).then_some(tr2.output()),
+                });
+            }
+        }
+    }
+}
+
+impl<A: Transducer, B: Transducer> Transducer for ComposedTransducer<A, B> {
+    fn lookup_encoded_with(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        self.lookup_encoded_report(input, options).map(|(analyses, _)| analyses)
+    }
+
+    fn lookup_encoded_report(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<(EncodedAnalyses, usize), Error> {
+        let search = self.search(options)?;
+        let analyses = search.run(input)?;
+        Ok((analyses, search.cycles_cut()))
+    }
+
+    fn lookup_encoded_each(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+        f: &mut dyn FnMut(Vec<Symbol>, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        self.search(options)?.run_each(input, f)
+    }
+
+    fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    fn input_letters(&self) -> &Trie<Symbol> {
+        self.first.input_letters()
+    }
+
+    fn unknown_symbols_start(&self) -> Symbol {
+        let first = self.first.unknown_symbols_start();
+        let second = self.alphabet.key_table().len() as Symbol;
+        first.max(second)
+    }
+
+    fn options(&self) -> &LookupOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LookupOptions {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::{String, ToString}, vec};
+    use crate::compile_att;
+    use crate::transducers::EpsilonCycles;
+
+    #[test]
+    fn epsilons_on_both_sides() {
+        // Deletes `h` and inserts `+Tag` after `a`
+        let first = compile_att("0\t0\th\t@0@\n0\t1\ta\ta\n1\t2\t@0@\t+Tag\t1\n2\n").unwrap();
+        // Inserts `<` before reading anything, and passes `+Tag` through
+        let second = compile_att("0\t1\t@0@\t<\n1\t2\ta\ta\n2\t3\t+Tag\t+Tag\t0.5\n3\n").unwrap();
+        let t = ComposedTransducer::new(first, second).unwrap();
+        assert_eq!(t.lookup("hha").unwrap(), vec![("<a+Tag".to_string(), 1.5)]);
+        assert_eq!(t.lookup("b").unwrap(), Vec::<(String, Weight)>::new());
+        assert_eq!(t.lookup("h").unwrap(), Vec::<(String, Weight)>::new());
+    }
+
+    #[test]
+    fn unknown_symbols() {
+        let first = compile_att("0\t0\t@_IDENTITY_SYMBOL_@\t@_IDENTITY_SYMBOL_@\n0\t0\ta\tb\n0\n").unwrap();
+        let second = compile_att("0\t1\t@_IDENTITY_SYMBOL_@\t@_IDENTITY_SYMBOL_@\n1\t1\tb\tc\n1\n").unwrap();
+        let t = ComposedTransducer::new(first, second).unwrap();
+        assert_eq!(t.lookup("жa").unwrap(), vec![("жc".to_string(), 0.0)]);
+
+        // `ö` is known to the first transducer only
+        let first = compile_att("0\t1\tö\tö\n1\n").unwrap();
+        let second = compile_att("0\t1\t@_IDENTITY_SYMBOL_@\t@_IDENTITY_SYMBOL_@\n1\n").unwrap();
+        assert_eq!(second.lookup("ö").unwrap(), vec![("ö".to_string(), 0.0)]);
+        let t = ComposedTransducer::new(first, second).unwrap();
+        assert_eq!(t.lookup("ö").unwrap(), vec![("ö".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn epsilon_cycles() {
+        let first = compile_att("0\t1\ta\ta\n1\t1\t@0@\t@0@\n1\n").unwrap();
+        let second = compile_att("0\t1\ta\tb\n1\n").unwrap();
+        let mut t = ComposedTransducer::new(first, second).unwrap();
+        let report = t.lookup_report("a", t.options()).unwrap();
+        assert_eq!(report.analyses, vec![("b".to_string(), 0.0)]);
+        assert_eq!(report.cycles_cut, 1);
+        t.options_mut().epsilon_cycles = EpsilonCycles::Fail;
+        assert!(matches!(t.lookup("a"), Err(Error::EpsilonCycle)));
+        t.options_mut().epsilon_cycles = EpsilonCycles::Follow(0);
+        assert_eq!(t.lookup("a").unwrap(), vec![("b".to_string(), 0.0)]);
+
+        // The cycle of the first transducer sets a flag, which lets the path
+        // leave it for `+Y`
+        let first = compile_att(concat!(
+            "0\t1\ta\ta\n",
+            "1\t2\t@P.F.X@\t@P.F.X@\n",
+            "2\t1\t@0@\t@0@\n",
+            "1\t3\t@R.F.X@\t+Y\n",
+            "3\n",
+        )).unwrap();
+        let second = compile_att("0\t1\ta\ta\n1\t2\t+Y\t+Y\n2\n").unwrap();
+        let t = ComposedTransducer::new(first, second).unwrap();
+        assert_eq!(t.lookup("a").unwrap(), vec![("a+Y".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn best_first() {
+        let first = compile_att("0\t1\ta\ta\n0\t1\ta\tb\t1\n1\n").unwrap();
+        let second = compile_att("0\t1\ta\tx\t3\n0\t1\tb\ty\n0\t1\tb\tz\t1\n1\n").unwrap();
+        let t = ComposedTransducer::new(first, second).unwrap();
+        assert_eq!(t.lookup("a").unwrap(), vec![
+            ("x".to_string(), 3.0),
+            ("y".to_string(), 1.0),
+            ("z".to_string(), 2.0),
+        ]);
+        let options = LookupOptions {
+            best_first: true,
+            max_analyses: 2,
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![
+            ("y".to_string(), 1.0),
+            ("z".to_string(), 2.0),
+        ]);
+    }
+
+    #[test]
+    fn pruning() {
+        let first = compile_att("0\t1\ta\ta\n0\t1\ta\tb\t1\n1\n").unwrap();
+        let second = compile_att("0\t1\ta\tx\t3\n0\t1\tb\ty\n0\t1\tb\tz\t1\n1\n").unwrap();
+        let t = ComposedTransducer::new(first, second).unwrap();
+        let options = LookupOptions {
+            max_weight: Some(2.0),
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![
+            ("y".to_string(), 1.0),
+            ("z".to_string(), 2.0),
+        ]);
+        let options = LookupOptions {
+            best_first: true,
+            max_analyses: 1,
+            ..options
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![("y".to_string(), 1.0)]);
+        let options = LookupOptions {
+            beam: Some(0.5),
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![("y".to_string(), 1.0)]);
+    }
+}
@@ -0,0 +1,196 @@
+use alloc::vec::Vec;
+use alloc::vec;
+
+/// Feature number of a flag diacritic.
+pub type Feature = u16;
+/// Value number of a flag diacritic.  `0` stands for the unset (neutral)
+/// value.
+pub type Value = i16;
+
+/// Operator of a flag diacritic, the letter after the first `@`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagDiacriticOperator {
+    /// `@P.FEAT.VAL@` sets the feature to the value.
+    Positive,
+    /// `@N.FEAT.VAL@` sets the feature to the negation of the value.
+    Negative,
+    /// `@R.FEAT.VAL@` requires the feature to be set to the value, and
+    /// `@R.FEAT@` requires it to be set to anything.
+    Require,
+    /// `@D.FEAT.VAL@` disallows the value, and `@D.FEAT@` disallows any value.
+    Disallow,
+    /// `@C.FEAT@` resets the feature.
+    Clear,
+    /// `@U.FEAT.VAL@` sets the feature if it is unset or compatible with the
+    /// value, and fails otherwise.
+    Unification,
+}
+
+impl FlagDiacriticOperator {
+    fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            b'P' => Some(Self::Positive),
+            b'N' => Some(Self::Negative),
+            b'R' => Some(Self::Require),
+            b'D' => Some(Self::Disallow),
+            b'C' => Some(Self::Clear),
+            b'U' => Some(Self::Unification),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed flag diacritic symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagDiacriticOperation {
+    operator: FlagDiacriticOperator,
+    feature: Feature,
+    value: Value,
+}
+
+impl FlagDiacriticOperation {
+    pub fn new(operator: FlagDiacriticOperator, feature: Feature, value: Value) -> Self {
+        FlagDiacriticOperation {
+            operator,
+            feature,
+            value,
+        }
+    }
+
+    /// Splits a symbol of form `@X.FEAT.VAL@` or `@X.FEAT@` into its
+    /// operator, feature name and value name.
+    ///
+    /// Returns [`None`] if the symbol is not a flag diacritic.
+    pub fn split(symbol: &str) -> Option<(FlagDiacriticOperator, &str, Option<&str>)> {
+        let bytes = symbol.as_bytes();
+        if bytes.len() < 5
+            || bytes[0] != b'@'
+            || bytes[bytes.len()-1] != b'@'
+            || bytes[2] != b'.'
+        {
+            return None;
+        }
+        let operator = FlagDiacriticOperator::from_byte(bytes[1])?;
+        let body = &symbol[3..symbol.len()-1];
+        let (feature, value) = match body.split_once('.') {
+            Some((feature, value)) => (feature, Some(value)),
+            None => (body, None),
+        };
+        if feature.is_empty() {
+            return None;
+        }
+        Some((operator, feature, value))
+    }
+
+    pub fn operator(&self) -> FlagDiacriticOperator {
+        self.operator
+    }
+    pub fn feature(&self) -> Feature {
+        self.feature
+    }
+    pub fn value(&self) -> Value {
+        self.value
+    }
+}
+
+/// Values of all features on a path through the transducer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagState(Vec<Value>);
+
+impl FlagState {
+    pub fn new(size: Feature) -> Self {
+        FlagState(vec![0; size as usize])
+    }
+
+    /// Applies an operation to the state.
+    ///
+    /// Returns `false` if the path is not allowed by the operation.  The state
+    /// is only changed when the operation succeeds.
+    pub fn apply(&mut self, op: &FlagDiacriticOperation) -> bool {
+        let Some(current) = self.0.get_mut(op.feature as usize) else {
+            return false;
+        };
+        let value = op.value;
+        match op.operator {
+            FlagDiacriticOperator::Positive => {
+                *current = value;
+                true
+            },
+            FlagDiacriticOperator::Negative => {
+                *current = -value;
+                true
+            },
+            FlagDiacriticOperator::Require => if value == 0 {
+                *current != 0
+            } else {
+                *current == value
+            },
+            FlagDiacriticOperator::Disallow => if value == 0 {
+                *current == 0
+            } else {
+                *current != value
+            },
+            FlagDiacriticOperator::Clear => {
+                *current = 0;
+                true
+            },
+            FlagDiacriticOperator::Unification => {
+                if *current == 0
+                    || *current == value
+                    || (*current < 0 && -*current != value)
+                {
+                    *current = value;
+                    true
+                } else {
+                    false
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            FlagDiacriticOperation::split("@U.Case.Nom@"),
+            Some((FlagDiacriticOperator::Unification, "Case", Some("Nom"))),
+        );
+        assert_eq!(
+            FlagDiacriticOperation::split("@C.Case@"),
+            Some((FlagDiacriticOperator::Clear, "Case", None)),
+        );
+        assert_eq!(FlagDiacriticOperation::split("@_EPSILON_SYMBOL_@"), None);
+        assert_eq!(FlagDiacriticOperation::split("+N"), None);
+    }
+
+    #[test]
+    fn unification() {
+        let set = FlagDiacriticOperation::new(FlagDiacriticOperator::Unification, 0, 1);
+        let other = FlagDiacriticOperation::new(FlagDiacriticOperator::Unification, 0, 2);
+        let negate = FlagDiacriticOperation::new(FlagDiacriticOperator::Negative, 0, 1);
+        let mut state = FlagState::new(1);
+        assert!(state.apply(&set));
+        assert!(state.apply(&set));
+        assert!(!state.apply(&other));
+        assert!(state.apply(&negate));
+        assert!(!state.apply(&set));
+        assert!(state.apply(&other));
+    }
+
+    #[test]
+    fn require_and_disallow() {
+        let require_any = FlagDiacriticOperation::new(FlagDiacriticOperator::Require, 0, 0);
+        let disallow_any = FlagDiacriticOperation::new(FlagDiacriticOperator::Disallow, 0, 0);
+        let set = FlagDiacriticOperation::new(FlagDiacriticOperator::Positive, 0, 3);
+        let mut state = FlagState::new(1);
+        assert!(!state.apply(&require_any));
+        assert!(state.apply(&disallow_any));
+        assert!(state.apply(&set));
+        assert!(state.apply(&require_any));
+        assert!(!state.apply(&disallow_any));
+    }
+}
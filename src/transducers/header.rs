@@ -1,16 +1,108 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    vec::Vec,
+};
 use nom::{
     IResult,
     bytes::complete::{tag, take},
     number::complete::{le_u16, le_u32},
     sequence::terminated,
-    combinator::opt,
+    combinator::verify,
+    error::{Error, ErrorKind},
 };
 
 use crate::parser_utils::{int_bool, parse_to_struct};
-use super::{Symbol, TransitionTableIndex};
+use super::{Symbol, TransitionTableIndex, Error as TransducerError};
+
+/// Key/value block of an HFST3 header, which precedes the transducer in files
+/// written by HFST tools.
+///
+/// Common keys are `version`, `type` and `name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hfst3Header {
+    properties: BTreeMap<String, String>,
+}
+
+impl Hfst3Header {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Hfst3Header> {
+        let (input, _) = tag(b"HFST\0")(input)?;
+        let (input, length) = terminated(le_u16, tag(b"\0"))(input)?;
+        let (input, block) = verify(
+            take(length),
+            |block: &[u8]| block.last() == Some(&0),
+        )(input)?;
+
+        let mut properties = BTreeMap::new();
+        let mut strings = block[..block.len()-1].split(|&b| b == 0);
+        while let Some(key) = strings.next() {
+            let value = strings.next()
+                .ok_or(nom::Err::Error(Error::new(input, ErrorKind::Verify)))?;
+            properties.insert(
+                String::from_utf8_lossy(key).into_owned(),
+                String::from_utf8_lossy(value).into_owned(),
+            );
+        }
+        Ok((input, Hfst3Header { properties }))
+    }
+
+    /// Fails with [`TransducerError::HeaderWritingError`] if the properties
+    /// take 64 KiB or more, which does not fit in the length field.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) -> Result<(), TransducerError> {
+        let mut block = Vec::new();
+        for (k, v) in self.iter() {
+            block.extend_from_slice(k.as_bytes());
+            block.push(0);
+            block.extend_from_slice(v.as_bytes());
+            block.push(0);
+        }
+        let length = u16::try_from(block.len())
+            .map_err(|_| TransducerError::HeaderWritingError)?;
+        out.extend_from_slice(b"HFST\0");
+        out.extend_from_slice(&length.to_le_bytes());
+        out.push(0);
+        out.extend(block);
+        Ok(())
+    }
 
+    /// Sets a property, returning the previous value.
+    pub fn insert(&mut self, key: &str, value: &str) -> Option<String> {
+        self.properties.insert(String::from(key), String::from(value))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    /// Iterates over all key/value pairs in the order of keys.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.properties.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Declared type of the transducer, such as `HFST_OL` or `HFST_OLW`.
+    pub fn transducer_type(&self) -> Option<&str> {
+        self.get("type")
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("name")
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.get("version")
+    }
+}
+
+/// Header of an optimized-lookup transducer: table sizes and properties.
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Header {
+    hfst3: Option<Hfst3Header>,
+
     number_of_input_symbols: Symbol,
     number_of_symbols: Symbol,
     size_of_transition_index_table: TransitionTableIndex,
@@ -31,17 +123,73 @@ pub struct Header {
 }
 
 impl Header {
-    fn skip_hfst3_header(input: &[u8]) -> IResult<&[u8], ()> {
-        let (input, _) = tag(b"HFST\0")(input)?;
-        let (input, length) = terminated(le_u16, tag(b"\0"))(input)?;
-        let (input, _) = terminated(take(length-1), tag(b"\0"))(input)?;
-        // Do nothing more for now
-        Ok((input, ()))
+    /// Creates a header with all properties except weightedness unset.
+    pub fn new(
+        number_of_input_symbols: Symbol,
+        number_of_symbols: Symbol,
+        size_of_transition_index_table: TransitionTableIndex,
+        size_of_transition_target_table: TransitionTableIndex,
+        number_of_states: u32,
+        number_of_transitions: u32,
+        weighted: bool,
+    ) -> Self {
+        Header {
+            hfst3: None,
+            number_of_input_symbols,
+            number_of_symbols,
+            size_of_transition_index_table,
+            size_of_transition_target_table,
+            number_of_states,
+            number_of_transitions,
+            weighted,
+            deterministic: false,
+            input_deterministic: false,
+            minimized: false,
+            cyclic: false,
+            has_epsilon_epsilon_transitions: false,
+            has_input_epsilon_transitions: false,
+            has_input_epsilon_cycles: false,
+            has_unweighted_input_epsilon_cycles: false,
+        }
+    }
+
+    pub fn set_hfst3(&mut self, hfst3: Option<Hfst3Header>) {
+        self.hfst3 = hfst3;
+    }
+
+    pub fn set_weighted(&mut self, weighted: bool) {
+        self.weighted = weighted;
+    }
+
+    pub fn set_epsilon_properties(
+        &mut self,
+        has_epsilon_epsilon_transitions: bool,
+        has_input_epsilon_transitions: bool,
+    ) {
+        self.has_epsilon_epsilon_transitions = has_epsilon_epsilon_transitions;
+        self.has_input_epsilon_transitions = has_input_epsilon_transitions;
+    }
+
+    /// Sets whether there are cycles of transitions with epsilon input, and
+    /// whether any of them has no weight.
+    pub fn set_cycle_properties(
+        &mut self,
+        has_input_epsilon_cycles: bool,
+        has_unweighted_input_epsilon_cycles: bool,
+    ) {
+        self.has_input_epsilon_cycles = has_input_epsilon_cycles;
+        self.has_unweighted_input_epsilon_cycles = has_unweighted_input_epsilon_cycles;
     }
 
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Header> {
-        let (input, _) = opt(Header::skip_hfst3_header)(input)?;
+    /// Parses the binary header, which follows the HFST3 header if there is
+    /// one.
+    pub(crate) fn parse(
+        input: &[u8],
+        hfst3: Option<Hfst3Header>,
+    ) -> IResult<&[u8], Header> {
+        let hfst3 = |input| Ok((input, hfst3));
         parse_to_struct!(input, Header {
+            hfst3: hfst3,
             number_of_input_symbols: le_u16,
             number_of_symbols: le_u16,
             size_of_transition_index_table: le_u32,
@@ -59,10 +207,41 @@ impl Header {
             has_unweighted_input_epsilon_cycles: int_bool,
         })
     }
+
+    /// Writes the header, preceded by the HFST3 header if there is one.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) -> Result<(), TransducerError> {
+        if let Some(hfst3) = &self.hfst3 {
+            hfst3.write(out)?;
+        }
+        out.extend_from_slice(&self.number_of_input_symbols.to_le_bytes());
+        out.extend_from_slice(&self.number_of_symbols.to_le_bytes());
+        out.extend_from_slice(&self.size_of_transition_index_table.to_le_bytes());
+        out.extend_from_slice(&self.size_of_transition_target_table.to_le_bytes());
+        out.extend_from_slice(&self.number_of_states.to_le_bytes());
+        out.extend_from_slice(&self.number_of_transitions.to_le_bytes());
+        for property in [
+            self.weighted,
+            self.deterministic,
+            self.input_deterministic,
+            self.minimized,
+            self.cyclic,
+            self.has_epsilon_epsilon_transitions,
+            self.has_input_epsilon_transitions,
+            self.has_input_epsilon_cycles,
+            self.has_unweighted_input_epsilon_cycles,
+        ] {
+            out.extend_from_slice(&(property as u32).to_le_bytes());
+        }
+        Ok(())
+    }
 }
 
 #[allow(dead_code)]
 impl Header {
+    /// HFST3 header metadata, if the transducer had one.
+    pub fn hfst3(&self) -> Option<&Hfst3Header> {
+        self.hfst3.as_ref()
+    }
     pub fn number_of_symbols(&self) -> Symbol {
         self.number_of_symbols
     }
@@ -103,3 +282,45 @@ impl Header {
         self.has_unweighted_input_epsilon_cycles
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Hfst3Header, TransducerError};
+    use alloc::vec::Vec;
+
+    pub(crate) fn hfst3_header(properties: &[(&str, &str)]) -> Vec<u8> {
+        let mut header = Hfst3Header::new();
+        for (k, v) in properties {
+            header.insert(k, v);
+        }
+        let mut res = Vec::new();
+        header.write(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn hfst3_properties() {
+        let mut input = hfst3_header(&[
+            ("version", "3.3"),
+            ("type", "HFST_OLW"),
+            ("name", "analyser"),
+        ]);
+        input.extend_from_slice(b"rest");
+        let (rest, header) = Hfst3Header::parse(&input).unwrap();
+        assert_eq!(rest, b"rest");
+        assert_eq!(header.version(), Some("3.3"));
+        assert_eq!(header.transducer_type(), Some("HFST_OLW"));
+        assert_eq!(header.name(), Some("analyser"));
+        assert_eq!(header.get("minimal"), None);
+    }
+
+    #[test]
+    fn oversized_hfst3_header() {
+        let mut header = Hfst3Header::new();
+        header.insert("name", &"a".repeat(u16::MAX as usize));
+        assert!(matches!(
+            header.write(&mut Vec::new()),
+            Err(TransducerError::HeaderWritingError)
+        ));
+    }
+}
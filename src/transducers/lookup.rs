@@ -0,0 +1,784 @@
+use alloc::{
+    vec::Vec,
+    collections::BinaryHeap,
+};
+use core::{cell::Cell, cmp::Ordering, ops::ControlFlow};
+use super::{
+    Error,
+    Alphabet,
+    LookupOptions, EpsilonCycles,
+    Header,
+    TransitionTableIndex,
+    Symbol, Weight, EncodedPath,
+    transitions::{TransitionTables, matching_transitions, reading_transitions},
+    flags::FlagState,
+    EPSILON,
+};
+
+/// Outputs with their weights, as found by a search.
+pub(crate) type Outputs<O> = Vec<(Vec<O>, Weight)>;
+
+/// Receiver of outputs as they are found.
+pub(crate) type Sink<'f, O> = dyn FnMut(Vec<O>, Weight) -> ControlFlow<()> + 'f;
+
+#[derive(Clone, Copy)]
+enum Stop {
+    /// No more analyses are wanted.
+    Done,
+    Budget,
+    Cancelled,
+    Overflow,
+    Cycle,
+}
+
+/// Counts steps of a search against [`LookupOptions::max_steps`], checks
+/// [`LookupOptions::cancel`], [`LookupOptions::max_output_length`] and
+/// [`LookupOptions::epsilon_cycles`].
+///
+/// It also keeps count of the analyses found for
+/// [`LookupOptions::max_analyses`], and the lightest of them for
+/// [`LookupOptions::beam`].
+struct Budget<'a> {
+    options: &'a LookupOptions,
+    steps: Cell<usize>,
+    stopped: Cell<Option<Stop>>,
+    /// Weight of the lightest analysis found so far.
+    best: Cell<Weight>,
+    /// Count of analyses found so far.
+    found: Cell<usize>,
+    /// Count of paths cut at input-epsilon cycles.
+    cycles_cut: Cell<usize>,
+}
+
+impl<'a> Budget<'a> {
+    fn new(options: &'a LookupOptions) -> Self {
+        Budget {
+            options,
+            steps: Cell::new(0),
+            stopped: Cell::new(None),
+            best: Cell::new(Weight::INFINITY),
+            found: Cell::new(0),
+            cycles_cut: Cell::new(0),
+        }
+    }
+
+    /// Counts a visited path.  Returns `false` if the search should stop.
+    fn step(&self) -> bool {
+        if self.stopped.get().is_some() {
+            return false;
+        }
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if self.options.max_steps > 0 && steps > self.options.max_steps {
+            self.stopped.set(Some(Stop::Budget));
+        } else if self.is_cancelled() {
+            self.stopped.set(Some(Stop::Cancelled));
+        }
+        self.stopped.get().is_none()
+    }
+
+    #[cfg(target_has_atomic = "ptr")]
+    fn is_cancelled(&self) -> bool {
+        self.options.cancel.as_ref().is_some_and(|hook| hook.is_cancelled())
+    }
+
+    #[cfg(not(target_has_atomic = "ptr"))]
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+
+    /// Checks the length of an output.  Returns `false` and stops the search
+    /// if it is too long.
+    fn fits(&self, length: usize) -> bool {
+        let max = self.options.max_output_length;
+        if max > 0 && length > max {
+            self.stopped.set(Some(Stop::Overflow));
+        }
+        self.stopped.get().is_none()
+    }
+
+    /// Checks a path that went around an input-epsilon cycle `times` times.
+    /// Returns `false` if the path should be cut, stopping the search if
+    /// cycles are not allowed at all.
+    fn allows_cycle(&self, times: usize) -> bool {
+        let allowed = match self.options.epsilon_cycles {
+            EpsilonCycles::Cut => false,
+            EpsilonCycles::Follow(max) => times <= max,
+            EpsilonCycles::Fail => {
+                self.stopped.set(Some(Stop::Cycle));
+                return false;
+            },
+        };
+        if !allowed {
+            self.cycles_cut.set(self.cycles_cut.get() + 1);
+        }
+        allowed
+    }
+
+    /// Stops the search without an error.
+    fn finish(&self) {
+        if self.stopped.get().is_none() {
+            self.stopped.set(Some(Stop::Done));
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.get().is_some()
+    }
+
+    /// Weight above which paths are pruned.
+    fn weight_limit(&self) -> Weight {
+        let cutoff = self.options.max_weight.unwrap_or(Weight::INFINITY);
+        let beam = self.options.beam.map_or(Weight::INFINITY, |beam| self.best.get() + beam);
+        cutoff.min(beam)
+    }
+
+    /// Passes a complete analysis to the sink unless it is pruned, and stops
+    /// the search if no more analyses are wanted.
+    fn emit<O>(&self, sink: &mut Sink<'_, O>, output: Vec<O>, weight: Weight) {
+        if self.is_stopped() || weight > self.weight_limit() {
+            return;
+        }
+        self.best.set(self.best.get().min(weight));
+        self.found.set(self.found.get() + 1);
+        let max_analyses = self.options.max_analyses;
+        if sink(output, weight).is_break()
+            || (max_analyses > 0 && self.found.get() >= max_analyses)
+        {
+            self.finish();
+        }
+    }
+
+    /// Drops analyses found before a lighter one put them out of the beam.
+    fn prune<A>(&self, mut analyses: Vec<(A, Weight)>) -> Vec<(A, Weight)> {
+        let limit = self.weight_limit();
+        analyses.retain(|analysis| analysis.1 <= limit);
+        analyses
+    }
+
+    /// Fails if the search was stopped before it was finished.
+    fn check(&self) -> Result<(), Error> {
+        match self.stopped.get() {
+            None | Some(Stop::Done) => Ok(()),
+            Some(Stop::Budget) => Err(Error::BudgetExceeded),
+            Some(Stop::Cancelled) => Err(Error::Cancelled),
+            Some(Stop::Overflow) => Err(Error::OutputLimitExceeded),
+            Some(Stop::Cycle) => Err(Error::EpsilonCycle),
+        }
+    }
+}
+
+/// Paths waiting to be explored.
+trait Frontier<P> {
+    fn push(&mut self, weight: Weight, path: P);
+    fn pop(&mut self) -> Option<(Weight, P)>;
+}
+
+/// Stack for depth-first searches, which pops the last pushed path first.
+impl<P> Frontier<P> for Vec<(Weight, P)> {
+    fn push(&mut self, weight: Weight, path: P) {
+        Vec::push(self, (weight, path));
+    }
+
+    fn pop(&mut self) -> Option<(Weight, P)> {
+        Vec::pop(self)
+    }
+}
+
+/// Queue of partial paths for best-first searches, which pops the lightest
+/// path first.
+struct Queue<P>(BinaryHeap<Queued<P>>);
+
+struct Queued<P> {
+    weight: Weight,
+    path: P,
+}
+
+impl<P> Frontier<P> for Queue<P> {
+    fn push(&mut self, weight: Weight, path: P) {
+        self.0.push(Queued { weight, path });
+    }
+
+    fn pop(&mut self) -> Option<(Weight, P)> {
+        self.0.pop().map(|queued| (queued.weight, queued.path))
+    }
+}
+
+impl<P> PartialEq for Queued<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<P> Eq for Queued<P> {}
+
+impl<P> PartialOrd for Queued<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P> Ord for Queued<P> {
+    /// Reversed, so that [`BinaryHeap`] pops the lightest path first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.weight.total_cmp(&self.weight)
+    }
+}
+
+/// Transition from a state of a [`Graph`].
+pub(crate) struct Move<G: Graph + ?Sized> {
+    pub weight: Weight,
+    /// Whether the transition consumes an input symbol.
+    pub consumed: bool,
+    pub target: G::State,
+    /// Flag state after the transition, if it was changed.
+    pub flags: Option<G::Flags>,
+    /// Symbol added to the output, if any.
+    pub output: Option<G::Output>,
+}
+
+/// States and transitions searched by [`Search`], such as the tables of a
+/// transducer.
+pub(crate) trait Graph {
+    /// State the search is in.  States which compare equal at the same
+    /// input position, with equal flag states, make a cycle.
+    type State: Copy + PartialEq;
+    type Flags: Clone + PartialEq;
+    /// Symbol of the output of a path.
+    type Output: Clone;
+
+    /// Start state, with the flag state it starts with.
+    fn start(&self) -> (Self::State, Self::Flags);
+
+    /// Final weight of a state, or `None` if it is not final.
+    fn final_weight(&self, state: Self::State) -> Option<Weight>;
+
+    /// Passes the transitions from a state to `f`, in table order.
+    /// Transitions not allowed by flag diacritics are left out.  `symbol` is
+    /// the next input symbol, if any is left.
+    fn moves(
+        &self,
+        state: Self::State,
+        flags: &Self::Flags,
+        symbol: Option<Symbol>,
+        f: &mut dyn FnMut(Move<Self>),
+    );
+}
+
+/// Partial path explored by [`Search`].
+struct Path<G: Graph> {
+    state: G::State,
+    /// Number of consumed input symbols.
+    position: usize,
+    flags: G::Flags,
+    output: Vec<G::Output>,
+    /// States visited at the current input position, with their flag
+    /// states.
+    visited: Vec<(G::State, G::Flags)>,
+    /// Whether the path is complete, with the final weight added.
+    complete: bool,
+}
+
+/// Search for the paths of a [`Graph`] which consume the input.
+///
+/// Paths are explored depth-first, in table order, or in order of weight if
+/// [`LookupOptions::best_first`] is set.  Paths waiting to be explored are
+/// kept on the heap, so the depth of the search is not bound by the native
+/// stack.
+pub(crate) struct Search<'a, G> {
+    graph: G,
+    options: &'a LookupOptions,
+    /// Whether paths are checked for input-epsilon cycles.
+    check_cycles: bool,
+    budget: Budget<'a>,
+}
+
+impl<'a, G: Graph> Search<'a, G> {
+    pub fn new(graph: G, options: &'a LookupOptions) -> Self {
+        Search {
+            graph,
+            options,
+            check_cycles: true,
+            budget: Budget::new(options),
+        }
+    }
+
+    /// Skips checks for input-epsilon cycles if the graph is known to have
+    /// none.
+    pub fn check_cycles(mut self, check_cycles: bool) -> Self {
+        self.check_cycles = check_cycles;
+        self
+    }
+
+    /// Number of paths cut at input-epsilon cycles so far.
+    pub fn cycles_cut(&self) -> usize {
+        self.budget.cycles_cut.get()
+    }
+
+    pub fn run(&self, input: &[Symbol]) -> Result<Outputs<G::Output>, Error> {
+        let mut outputs = Vec::new();
+        self.run_each(input, &mut |output, weight| {
+            outputs.push((output, weight));
+            ControlFlow::Continue(())
+        })?;
+        Ok(self.budget.prune(outputs))
+    }
+
+    /// Passes outputs to `sink` as they are found, until it breaks.
+    ///
+    /// Unlike [`Search::run()`], outputs found before a lighter one put them
+    /// out of the beam are not taken back.
+    pub fn run_each(&self, input: &[Symbol], sink: &mut Sink<'_, G::Output>) -> Result<(), Error> {
+        let (state, flags) = self.graph.start();
+        let start = Path {
+            state,
+            position: 0,
+            flags,
+            output: Vec::new(),
+            visited: Vec::new(),
+            complete: false,
+        };
+        if self.options.best_first {
+            self.explore(input, Queue(BinaryHeap::new()), start, sink);
+        } else {
+            self.explore(input, Vec::new(), start, sink);
+        }
+        self.budget.check()
+    }
+
+    /// Explores paths in the order `frontier` pops them.  From a stack,
+    /// paths going on with epsilons are found before the path ending in the
+    /// same state, and paths are found in table order.
+    fn explore<Q>(
+        &self,
+        input: &[Symbol],
+        mut frontier: Q,
+        start: Path<G>,
+        sink: &mut Sink<'_, G::Output>,
+    )
+    where
+        Q: Frontier<Path<G>>,
+    {
+        frontier.push(0.0, start);
+        while let Some((weight, path)) = frontier.pop() {
+            if !self.budget.step() {
+                break;
+            }
+            if path.complete {
+                self.budget.emit(sink, path.output, weight);
+                continue;
+            }
+            if path.position == input.len() {
+                if let Some(final_weight) = self.graph.final_weight(path.state)
+                    .filter(|final_weight| weight + final_weight <= self.budget.weight_limit())
+                {
+                    frontier.push(weight + final_weight, Path {
+                        flags: path.flags.clone(),
+                        output: path.output.clone(),
+                        visited: Vec::new(),
+                        complete: true,
+                        ..path
+                    });
+                }
+            }
+            for (weight, next) in self.expand(input, weight, &path).into_iter().rev() {
+                frontier.push(weight, next);
+            }
+        }
+    }
+
+    /// Returns the paths following a path of the given weight, with their
+    /// weights, in table order.  Paths heavier than the weight limit are
+    /// pruned.
+    fn expand(
+        &self,
+        input: &[Symbol],
+        weight: Weight,
+        path: &Path<G>,
+    ) -> Vec<(Weight, Path<G>)> {
+        let limit = self.budget.weight_limit();
+        let mut next = Vec::new();
+        self.graph.moves(path.state, &path.flags, input.get(path.position).copied(), &mut |step| {
+            let weight = weight + step.weight;
+            if weight > limit || self.budget.is_stopped() {
+                return;
+            }
+            let Some(visited) = self.visit(path, &step) else {
+                return;
+            };
+            let mut output = path.output.clone();
+            if let Some(symbol) = step.output {
+                if !self.budget.fits(output.len() + 1) {
+                    return;
+                }
+                output.push(symbol);
+            }
+            next.push((weight, Path {
+                state: step.target,
+                position: path.position + usize::from(step.consumed),
+                flags: step.flags.unwrap_or_else(|| path.flags.clone()),
+                output,
+                visited,
+                complete: false,
+            }));
+        });
+        next
+    }
+
+    /// States visited at the input position after a move, or `None` if the
+    /// move would go around a cycle too many times.
+    ///
+    /// A state reached again with different flag diacritic values is not a
+    /// cycle, since the values may let the path leave it another way.
+    fn visit(
+        &self,
+        path: &Path<G>,
+        step: &Move<G>,
+    ) -> Option<Vec<(G::State, G::Flags)>> {
+        if !self.check_cycles || step.consumed {
+            return Some(Vec::new());
+        }
+        let flags = step.flags.as_ref().unwrap_or(&path.flags);
+        let times = path.visited.iter()
+            .map(|(state, flags)| (state, flags))
+            .chain(Some((&path.state, &path.flags)))
+            .filter(|&(&state, visited_flags)| state == step.target && visited_flags == flags)
+            .count();
+        if times > 0 && !self.budget.allows_cycle(times) {
+            return None;
+        }
+        let mut visited = path.visited.clone();
+        visited.push((path.state, path.flags.clone()));
+        Some(visited)
+    }
+}
+
+/// Transition tables of one transducer, searched by [`Lookup`].
+pub(crate) struct Tables<'a, T: ?Sized> {
+    tables: &'a T,
+    alphabet: &'a Alphabet,
+    show_flags: bool,
+}
+
+impl<T: TransitionTables + ?Sized> Graph for Tables<'_, T> {
+    type State = TransitionTableIndex;
+    type Flags = FlagState;
+    /// Input and output symbols of a transition.  Consumed input symbols are
+    /// given as they are in the input, so unknown symbols are not replaced
+    /// by identity or unknown ones.
+    type Output = (Symbol, Symbol);
+
+    fn start(&self) -> (Self::State, Self::Flags) {
+        (0, FlagState::new(self.alphabet.state_size()))
+    }
+
+    fn final_weight(&self, state: Self::State) -> Option<Weight> {
+        self.tables.final_weight(state)
+    }
+
+    fn moves(
+        &self,
+        state: Self::State,
+        flags: &Self::Flags,
+        symbol: Option<Symbol>,
+        f: &mut dyn FnMut(Move<Self>),
+    ) {
+        let epsilons = matching_transitions(self.tables, self.alphabet, state, EPSILON)
+            .map(|tr| (tr, None));
+        let consumed = symbol
+            .map(|symbol| reading_transitions(self.tables, self.alphabet, state, symbol)
+                .map(move |tr| (tr, Some(symbol))))
+            .into_iter()
+            .flatten();
+        for (tr, read) in epsilons.chain(consumed) {
+            // Flag diacritics are traversed like epsilons if the path
+            // satisfies them
+            let flags = match self.alphabet.flag_diacritic(tr.input()) {
+                Some(op) => {
+                    let mut flags = flags.clone();
+                    if !flags.apply(op) {
+                        continue;
+                    }
+                    Some(flags)
+                },
+                None => None,
+            };
+            let shown = self.show_flags || !self.alphabet.is_flag(tr.output());
+            f(Move {
+                weight: tr.weight(),
+                consumed: read.is_some(),
+                target: tr.target(),
+                flags,
+                output: shown.then(|| (read.unwrap_or(tr.input()), tr.output())),
+            });
+        }
+    }
+}
+
+/// Search over the tables of a transducer, shared by all implementations.
+pub struct Lookup<'a, T: ?Sized> {
+    search: Search<'a, Tables<'a, T>>,
+}
+
+impl<'a, T: TransitionTables + ?Sized> Lookup<'a, T> {
+    pub fn new(tables: &'a T, alphabet: &'a Alphabet, options: &'a LookupOptions) -> Self {
+        let tables = Tables {
+            tables,
+            alphabet,
+            show_flags: options.show_flags,
+        };
+        Lookup {
+            search: Search::new(tables, options),
+        }
+    }
+
+    /// Skips checks for input-epsilon cycles if the header says there are
+    /// none.
+    pub fn with_header(self, header: &Header) -> Self {
+        Lookup {
+            search: self.search.check_cycles(
+                header.has_input_epsilon_cycles() || header.has_unweighted_input_epsilon_cycles()
+            ),
+        }
+    }
+
+    /// Number of paths cut at input-epsilon cycles so far.
+    pub fn cycles_cut(&self) -> usize {
+        self.search.cycles_cut()
+    }
+
+    pub fn run(&self, input: &[Symbol]) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        let paths = self.search.run(input)?;
+        Ok(paths.into_iter().map(|(path, weight)| (outputs(path), weight)).collect())
+    }
+
+    /// Passes analyses to `sink` as they are found, until it breaks.
+    ///
+    /// Unlike [`Lookup::run()`], analyses found before a lighter one put them
+    /// out of the beam are not taken back.
+    pub fn run_each(&self, input: &[Symbol], sink: &mut Sink<'_, Symbol>) -> Result<(), Error> {
+        self.search.run_each(input, &mut |path, weight| sink(outputs(path), weight))
+    }
+
+    /// Collects paths with the input symbol of every transition.  Consumed
+    /// input symbols are given as they are in `input`, so unknown symbols
+    /// are not replaced by identity or unknown ones.
+    pub fn run_aligned(&self, input: &[Symbol]) -> Result<Vec<(EncodedPath, Weight)>, Error> {
+        self.search.run(input)
+    }
+}
+
+/// Output symbols of a path.
+fn outputs(path: EncodedPath) -> Vec<Symbol> {
+    path.into_iter().map(|(_, output)| output).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use crate::{compile_att, Error, LookupOptions, Transducer};
+    use crate::transducers::EpsilonCycles;
+
+    #[test]
+    fn best_first() {
+        let t = compile_att(
+            "0\t1\ta\tx\t3\n0\t1\ta\ty\t1\n0\t2\t@0@\t@0@\n2\t1\ta\tz\t1.5\n1\n"
+        ).unwrap();
+        let options = LookupOptions {
+            best_first: true,
+            max_analyses: 2,
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![
+            ("y".to_string(), 1.0),
+            ("z".to_string(), 1.5),
+        ]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn small_stack() {
+        let mut att = std::string::String::new();
+        for i in 0..900 {
+            att.push_str(&alloc::format!("{i}\t{}\t@0@\tx\n", i + 1));
+        }
+        att.push_str("900\t901\ta\ta\n901\n");
+        let t = compile_att(&att).unwrap();
+        let analyses = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || t.lookup("a").unwrap())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(analyses.len(), 1);
+        assert_eq!(analyses[0].0.len(), 901);
+    }
+
+    #[test]
+    fn budget() {
+        let t = compile_att("0\t0\t@0@\tx\n0\t1\ta\ta\n1\n").unwrap();
+        let options = LookupOptions {
+            max_steps: 100,
+            epsilon_cycles: EpsilonCycles::Follow(usize::MAX),
+            ..LookupOptions::default()
+        };
+        assert!(matches!(t.lookup_with("a", &options), Err(Error::BudgetExceeded)));
+    }
+
+    #[test]
+    fn output_limit() {
+        let t = compile_att("0\t0\t@0@\tx\n0\t1\ta\ta\n1\n").unwrap();
+        let options = LookupOptions {
+            epsilon_cycles: EpsilonCycles::Follow(usize::MAX),
+            ..LookupOptions::default()
+        };
+        assert!(matches!(t.lookup_with("a", &options), Err(Error::OutputLimitExceeded)));
+
+        let t = compile_att("0\t1\ta\tx\n1\t2\t@0@\ty\n2\n").unwrap();
+        let options = LookupOptions {
+            max_output_length: 1,
+            ..LookupOptions::default()
+        };
+        assert!(matches!(t.lookup_with("a", &options), Err(Error::OutputLimitExceeded)));
+        let options = LookupOptions {
+            max_output_length: 2,
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![("xy".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn epsilon_cycles() {
+        let t = compile_att("0\t1\ta\ta\n1\t2\t@0@\tx\n2\t1\t@0@\t@0@\n1\n").unwrap();
+        assert!(t.header().unwrap().has_input_epsilon_cycles());
+        assert_eq!(t.lookup("a").unwrap(), vec![("a".to_string(), 0.0)]);
+        let report = t.lookup_report("a", t.options()).unwrap();
+        assert_eq!(report.analyses, vec![("a".to_string(), 0.0)]);
+        assert_eq!(report.cycles_cut, 1);
+        let options = LookupOptions {
+            epsilon_cycles: EpsilonCycles::Fail,
+            ..LookupOptions::default()
+        };
+        assert!(matches!(t.lookup_with("a", &options), Err(Error::EpsilonCycle)));
+
+        for best_first in [false, true] {
+            let options = LookupOptions {
+                epsilon_cycles: EpsilonCycles::Follow(1),
+                best_first,
+                ..LookupOptions::default()
+            };
+            let mut report = t.lookup_report("a", &options).unwrap();
+            report.analyses.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(report.analyses, vec![
+                ("a".to_string(), 0.0),
+                ("ax".to_string(), 0.0),
+            ]);
+            assert_eq!(report.cycles_cut, 1);
+        }
+
+        // The cycle sets a flag, which lets the path leave it for `+Y`
+        let t = compile_att(concat!(
+            "0\t1\ta\ta\n",
+            "1\t2\t@P.F.X@\t@P.F.X@\n",
+            "2\t1\t@0@\t@0@\n",
+            "1\t3\t@R.F.X@\t+Y\n",
+            "3\n",
+        )).unwrap();
+        for best_first in [false, true] {
+            let options = LookupOptions {
+                best_first,
+                ..LookupOptions::default()
+            };
+            let report = t.lookup_report("a", &options).unwrap();
+            assert_eq!(report.analyses, vec![("a+Y".to_string(), 0.0)]);
+            assert_eq!(report.cycles_cut, 1);
+        }
+
+        // Outputs `b` and goes around a cycle inserting `c` and `d`
+        let t = compile_att("0\t1\ta\tb\n1\t2\t@0@\tc\n2\t1\t@0@\td\n1\n").unwrap();
+        for best_first in [false, true] {
+            let options = LookupOptions {
+                best_first,
+                ..LookupOptions::default()
+            };
+            let report = t.lookup_report("a", &options).unwrap();
+            assert_eq!(report.analyses, vec![("b".to_string(), 0.0)]);
+            assert_eq!(report.cycles_cut, 1);
+        }
+    }
+
+    #[test]
+    fn unknown_symbols() {
+        let t = compile_att(concat!(
+            "0\t0\ta\tb\n",
+            "0\t0\t@_IDENTITY_SYMBOL_@\t@_IDENTITY_SYMBOL_@\n",
+            "0\t1\t@_UNKNOWN_SYMBOL_@\t?\t1\n",
+            "0\n1\n",
+        )).unwrap();
+        let (tokens, unknown) = t.tokenize_with_unknown("éaéж").unwrap();
+        assert_eq!(tokens[0], tokens[2]);
+        assert_eq!(unknown, vec!["é".to_string(), "ж".to_string()]);
+        assert!(matches!(t.tokenize("é"), Err(Error::TokenizationError)));
+
+        for best_first in [false, true] {
+            let options = LookupOptions {
+                best_first,
+                ..LookupOptions::default()
+            };
+            assert_eq!(t.lookup_with("éaж", &options).unwrap(), vec![
+                ("ébж".to_string(), 0.0),
+                ("éb?".to_string(), 1.0),
+            ]);
+        }
+        let t = compile_att("0\t1\ta\tb\n1\n").unwrap();
+        assert_eq!(t.lookup("ж").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn aligned() {
+        let t = compile_att(concat!(
+            "0\t1\ta\tx\n",
+            "1\t2\t@0@\t+N\n",
+            "2\t3\t@_IDENTITY_SYMBOL_@\t@_IDENTITY_SYMBOL_@\n",
+            "3\t4\tb\t@0@\n",
+            "4\n",
+        )).unwrap();
+        for best_first in [false, true] {
+            let options = LookupOptions {
+                best_first,
+                ..LookupOptions::default()
+            };
+            let paths = t.lookup_aligned("aжb", &options).unwrap();
+            assert_eq!(paths.len(), 1);
+            let pairs: Vec<_> = paths[0].0.iter()
+                .map(|pair| (pair.input.as_str(), pair.output.as_str(), pair.span.clone()))
+                .collect();
+            assert_eq!(pairs, [
+                ("a", "x", 0..1),
+                ("", "+N", 1..1),
+                ("ж", "ж", 1..3),
+                ("b", "", 3..4),
+            ]);
+        }
+    }
+
+    #[test]
+    fn pruning() {
+        let t = compile_att(
+            "0\t1\ta\tx\t3\n0\t1\ta\ty\t1\n0\t2\t@0@\t@0@\n2\t1\ta\tz\t1.5\n1\n"
+        ).unwrap();
+        let options = LookupOptions {
+            max_weight: Some(2.0),
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![
+            ("z".to_string(), 1.5),
+            ("y".to_string(), 1.0),
+        ]);
+        let options = LookupOptions {
+            beam: Some(0.25),
+            ..LookupOptions::default()
+        };
+        assert_eq!(t.lookup_with("a", &options).unwrap(), vec![("y".to_string(), 1.0)]);
+    }
+}
@@ -0,0 +1,251 @@
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use super::{
+    Transducer,
+    Error,
+    Header, Alphabet,
+    LookupOptions, Trie,
+    TransitionIndex,
+    TransitionTableIndex,
+    Symbol, Weight,
+    transitions::{UnweightedTransition, WeightedTransition, TransitionTables},
+    lookup::Lookup,
+    parse_prologue,
+};
+
+/// Transducer reading its transition tables straight from a byte buffer,
+/// without copying them.
+///
+/// The buffer can be anything that dereferences to bytes: a borrowed
+/// `&[u8]`, an `Arc<[u8]>` shared between threads, or a memory-mapped file
+/// (see `MappedTransducer::open()`, available with the `mmap` feature).
+/// Only the header and the symbol table are parsed upfront, so loading is
+/// almost instant.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use hfstol::{Transducer, transducers::MappedTransducer};
+///
+/// let content: Arc<[u8]> = std::fs::read("./analyser-gt-desc.hfstol").unwrap().into();
+/// let t = MappedTransducer::new(content).unwrap();
+/// println!("{:?}", t.lookup("лэсьтӥськонъёс"));
+/// ```
+pub struct MappedTransducer<B> {
+    buffer: B,
+    header: Header,
+    alphabet: Alphabet,
+    input_letters: Trie<Symbol>,
+    weighted: bool,
+    index_start: usize,
+    transitions_start: usize,
+    end: usize,
+    options: LookupOptions,
+}
+
+impl<B: AsRef<[u8]>> MappedTransducer<B> {
+    /// Reads the transducer at the start of `buffer`.
+    ///
+    /// Fails with [`Error::TableParsingError`] if the buffer is too short to
+    /// contain the tables declared in the header.
+    pub fn new(buffer: B) -> Result<Self, Error> {
+        let bytes = buffer.as_ref();
+        let (rest, header, alphabet, weighted) = parse_prologue(bytes)?;
+        let index_start = bytes.len() - rest.len();
+        let transitions_start = table_end(
+            index_start,
+            header.size_of_transition_index_table(),
+            TransitionIndex::SIZE,
+        )?;
+        let end = table_end(
+            transitions_start,
+            header.size_of_transition_target_table(),
+            Self::transition_size(weighted),
+        )?;
+        if end > bytes.len() {
+            return Err(Error::TableParsingError);
+        }
+        let input_letters = alphabet.to_trie(header.number_of_input_symbols());
+        Ok(MappedTransducer {
+            buffer,
+            header,
+            alphabet,
+            input_letters,
+            weighted,
+            index_start,
+            transitions_start,
+            end,
+            options: LookupOptions::default(),
+        })
+    }
+
+    /// Number of bytes the transducer takes in the buffer.
+    pub fn size(&self) -> usize {
+        self.end
+    }
+
+    pub fn buffer(&self) -> &B {
+        &self.buffer
+    }
+
+    fn transition_size(weighted: bool) -> usize {
+        if weighted {
+            WeightedTransition::SIZE
+        } else {
+            UnweightedTransition::SIZE
+        }
+    }
+
+    fn entry(&self, start: usize, size: usize, index: TransitionTableIndex) -> &[u8] {
+        let start = start + index as usize * size;
+        &self.buffer.as_ref()[start..start + size]
+    }
+}
+
+/// Offset of the end of a table of `size` entries starting at `start`.
+///
+/// Fails with [`Error::TableParsingError`] if it does not fit in `usize`,
+/// which can happen on 32-bit targets.
+fn table_end(start: usize, size: TransitionTableIndex, entry_size: usize) -> Result<usize, Error> {
+    (size as usize).checked_mul(entry_size)
+        .and_then(|len| start.checked_add(len))
+        .ok_or(Error::TableParsingError)
+}
+
+#[cfg(feature = "mmap")]
+impl MappedTransducer<memmap2::Mmap> {
+    /// Maps a transducer file into memory.
+    ///
+    /// Pages of the file are shared between all processes mapping it.  The
+    /// file must not be modified while it is mapped.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(Error::IoError)?;
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(Error::IoError)?;
+        MappedTransducer::new(mmap)
+    }
+}
+
+impl<B: AsRef<[u8]>> TransitionTables for MappedTransducer<B> {
+    fn weighted(&self) -> bool {
+        self.weighted
+    }
+
+    fn index_table_size(&self) -> TransitionTableIndex {
+        self.header.size_of_transition_index_table()
+    }
+
+    fn target_table_size(&self) -> TransitionTableIndex {
+        self.header.size_of_transition_target_table()
+    }
+
+    fn transition_index(&self, index: TransitionTableIndex) -> Option<TransitionIndex> {
+        if index >= self.index_table_size() {
+            return None;
+        }
+        let bytes = self.entry(self.index_start, TransitionIndex::SIZE, index);
+        TransitionIndex::parse(bytes).ok().map(|(_, tr)| tr)
+    }
+
+    fn transition(&self, index: TransitionTableIndex) -> Option<WeightedTransition> {
+        if index >= self.target_table_size() {
+            return None;
+        }
+        if self.weighted {
+            let bytes = self.entry(self.transitions_start, WeightedTransition::SIZE, index);
+            WeightedTransition::parse(bytes).ok().map(|(_, tr)| tr)
+        } else {
+            let bytes = self.entry(self.transitions_start, UnweightedTransition::SIZE, index);
+            UnweightedTransition::parse(bytes).ok().map(|(_, tr)| tr.into())
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> Transducer for MappedTransducer<B> {
+    fn lookup_encoded_with(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        Lookup::new(self, &self.alphabet, options).with_header(&self.header).run(input)
+    }
+
+    fn lookup_encoded_each(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+        f: &mut dyn FnMut(Vec<Symbol>, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        Lookup::new(self, &self.alphabet, options).with_header(&self.header).run_each(input, f)
+    }
+
+    fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    fn header(&self) -> Option<&Header> {
+        Some(&self.header)
+    }
+
+    fn tables(&self) -> Option<&dyn TransitionTables> {
+        Some(self)
+    }
+
+    fn input_letters(&self) -> &Trie<u16> {
+        &self.input_letters
+    }
+
+    fn options(&self) -> &LookupOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LookupOptions {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, string::String};
+    use super::*;
+    use crate::transducers::{write_transducer, WriteOptions, tests::a_to_b};
+
+    #[test]
+    fn borrowed_buffer() {
+        for weighted in [true, false] {
+            let bytes = write_transducer(&a_to_b(), &WriteOptions {
+                weighted,
+                hfst3_header: true,
+            }).unwrap();
+            let t = MappedTransducer::new(&bytes[..]).unwrap();
+            assert_eq!(t.size(), bytes.len());
+            let weight = if weighted { 1.5 } else { 0.0 };
+            assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), weight)]);
+            assert!(MappedTransducer::new(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn oversized_tables() {
+        let mut bytes = vec![];
+        Header::new(2, 3, u32::MAX, u32::MAX, 2, 1, true).write(&mut bytes).unwrap();
+        a_to_b().alphabet().write(&mut bytes);
+        assert!(matches!(MappedTransducer::new(&bytes[..]), Err(Error::TableParsingError)));
+    }
+
+    #[test]
+    fn table_overflow() {
+        assert_eq!(table_end(10, 2, WeightedTransition::SIZE).unwrap(), 10 + 2 * WeightedTransition::SIZE);
+        assert!(matches!(table_end(usize::MAX - 1, 1, TransitionIndex::SIZE), Err(Error::TableParsingError)));
+        assert!(matches!(table_end(0, 2, usize::MAX), Err(Error::TableParsingError)));
+    }
+
+    #[test]
+    fn shared_buffer() {
+        let bytes: alloc::sync::Arc<[u8]> = write_transducer(&a_to_b(), &WriteOptions {
+            weighted: true,
+            hfst3_header: false,
+        }).unwrap().into();
+        let t = MappedTransducer::new(bytes.clone()).unwrap();
+        assert_eq!(t.size(), bytes.len());
+        assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), 1.5)]);
+    }
+}
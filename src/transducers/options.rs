@@ -0,0 +1,270 @@
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(target_has_atomic = "ptr")]
+use core::fmt;
+use super::Weight;
+
+/// Default value of [`LookupOptions::max_output_length`].
+pub const DEFAULT_MAX_OUTPUT_LENGTH: usize = 1_000;
+
+/// Options controlling a lookup.
+///
+/// Every transducer keeps a set of options used by [`super::Transducer::lookup()`],
+/// which can be changed with [`super::Transducer::options_mut()`].  Options
+/// for a single lookup can be passed to [`super::Transducer::lookup_with()`].
+///
+/// ```
+/// use hfstol::LookupOptions;
+///
+/// let options = LookupOptions {
+///     show_flags: true,
+///     ..LookupOptions::default()
+/// };
+/// assert_eq!(options.max_analyses, 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LookupOptions {
+    /// Maximum count of analyses performed.
+    ///
+    /// There might be a bit more analyses than `max_analyses` performed and
+    /// added to the results.  `0` means no limit is imposed.
+    pub max_analyses: usize,
+
+    /// Whether flag diacritics are kept in the output.  They are omitted by
+    /// default.
+    pub show_flags: bool,
+
+    /// Whether paths are explored in order of their weight, lightest first.
+    ///
+    /// Analyses are then returned sorted by weight, and `max_analyses` keeps
+    /// the lightest ones instead of the first ones found.  Weights are
+    /// expected to be non-negative, as in the tropical semiring.
+    pub best_first: bool,
+
+    /// Paths heavier than this weight are pruned during the search.
+    pub max_weight: Option<Weight>,
+
+    /// Paths heavier than the lightest analysis found so far by more than
+    /// this weight are pruned during the search, and such analyses are
+    /// dropped from the results.
+    pub beam: Option<Weight>,
+
+    /// Maximum count of partial paths explored by a lookup.  When it is
+    /// exceeded, the lookup fails with [`super::Error::BudgetExceeded`].
+    /// `0` means no limit is imposed.
+    pub max_steps: usize,
+
+    /// Hook checked during a lookup.  If it reports cancellation, the lookup
+    /// fails with [`super::Error::Cancelled`].
+    ///
+    /// Only available on targets with atomic pointers, see [`CancelHook`].
+    #[cfg(target_has_atomic = "ptr")]
+    pub cancel: Option<CancelHook>,
+
+    /// Maximum number of symbols in an output.  A path producing a longer
+    /// output fails the lookup with [`super::Error::OutputLimitExceeded`],
+    /// which usually means the lexicon has an output cycle.  `0` means no
+    /// limit is imposed.
+    ///
+    /// Defaults to [`DEFAULT_MAX_OUTPUT_LENGTH`].
+    pub max_output_length: usize,
+
+    /// What to do when a path returns to a state without consuming input.
+    pub epsilon_cycles: EpsilonCycles,
+
+    /// Whether an input without analyses is looked up again with its first
+    /// letter lowercased, and then lowercased entirely, so that
+    /// sentence-initial and uppercase words are found in a lowercase lexicon.
+    /// [`super::Transducer::lookup_cased()`] reports the variant which matched.
+    ///
+    /// Lowercasing applies to the whole input, so generators should be given
+    /// lemmas whose tags are not cased.
+    pub case_fallback: bool,
+
+    /// Whether outputs of a lowercased variant of the input get the case of
+    /// the input back, as in generation from a capitalized lemma.  Tags of
+    /// the outputs keep their case.
+    pub restore_case: bool,
+}
+
+/// Handling of input-epsilon cycles, which would otherwise make a lookup
+/// follow a path forever.
+///
+/// A cycle is detected when a path visits the same state twice at one input
+/// position, with the same values of flag diacritics.  Transducers whose
+/// header says they have no input-epsilon cycles are not checked.
+///
+/// Paths cut at cycles are counted by [`super::Transducer::lookup_report()`].
+/// Other lookups do not tell whether paths were cut, so their results may be
+/// incomplete for transducers with cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EpsilonCycles {
+    /// Cut paths going around a cycle, keeping the analyses of the other
+    /// paths.
+    #[default]
+    Cut,
+    /// Go around a cycle at most the given number of times on a path.  Paths
+    /// going around it more times are cut.
+    Follow(usize),
+    /// Fail the lookup with [`super::Error::EpsilonCycle`].
+    Fail,
+}
+
+/// Variant of the input tried by a lookup with
+/// [`LookupOptions::case_fallback`], in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseVariant {
+    /// The input as given.
+    AsGiven,
+    /// The input with its first letter lowercased.
+    FirstLowercased,
+    /// The input lowercased entirely.
+    Lowercased,
+}
+
+impl CaseVariant {
+    pub const ALL: [CaseVariant; 3] = [
+        CaseVariant::AsGiven,
+        CaseVariant::FirstLowercased,
+        CaseVariant::Lowercased,
+    ];
+
+    /// Variants of an input to look up in order, skipping ones that give
+    /// the same string.  Only the input as given is looked up without a
+    /// fallback.
+    pub(crate) fn variants(input: &str, fallback: bool) -> Vec<(CaseVariant, String)> {
+        let count = if fallback { CaseVariant::ALL.len() } else { 1 };
+        let mut variants: Vec<(CaseVariant, String)> = Vec::new();
+        for variant in CaseVariant::ALL.into_iter().take(count) {
+            let candidate = variant.apply(input);
+            if variants.iter().all(|(_, tried)| *tried != candidate) {
+                variants.push((variant, candidate));
+            }
+        }
+        variants
+    }
+
+    /// Returns the variant of an input.
+    pub fn apply(self, input: &str) -> String {
+        match self {
+            CaseVariant::AsGiven => input.to_string(),
+            CaseVariant::FirstLowercased => {
+                let mut chars = input.chars();
+                chars.next()
+                    .map(|c| c.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            },
+            CaseVariant::Lowercased => input.to_lowercase(),
+        }
+    }
+
+    /// Gives an output of this variant of `input` the case of `input`.  Only
+    /// all-uppercase inputs make the whole output uppercase, otherwise its
+    /// first letter is capitalized.
+    ///
+    /// The output is changed as a whole, tags included.  Lookups with
+    /// [`LookupOptions::restore_case`] leave the tags as they are.
+    pub fn restore(self, output: &str, input: &str) -> String {
+        self.restoration(input).apply(output)
+    }
+
+    /// How outputs of this variant of `input` get the case of `input` back.
+    pub(crate) fn restoration(self, input: &str) -> Restoration {
+        let uppercase = input.chars().any(char::is_uppercase)
+            && !input.chars().any(char::is_lowercase);
+        match self {
+            CaseVariant::AsGiven => Restoration::Keep,
+            CaseVariant::Lowercased if uppercase => Restoration::Uppercase,
+            CaseVariant::FirstLowercased | CaseVariant::Lowercased => Restoration::Capitalize,
+        }
+    }
+}
+
+/// Case change restoring an input, see [`CaseVariant::restore()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Restoration {
+    Keep,
+    Capitalize,
+    Uppercase,
+}
+
+impl Restoration {
+    pub fn apply(self, output: &str) -> String {
+        match self {
+            Restoration::Keep => output.to_string(),
+            Restoration::Capitalize => {
+                let mut chars = output.chars();
+                chars.next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            },
+            Restoration::Uppercase => output.to_uppercase(),
+        }
+    }
+}
+
+impl Default for LookupOptions {
+    fn default() -> Self {
+        LookupOptions {
+            max_analyses: 0,
+            show_flags: false,
+            best_first: false,
+            max_weight: None,
+            beam: None,
+            max_steps: 0,
+            #[cfg(target_has_atomic = "ptr")]
+            cancel: None,
+            max_output_length: DEFAULT_MAX_OUTPUT_LENGTH,
+            epsilon_cycles: EpsilonCycles::default(),
+            case_fallback: false,
+            restore_case: false,
+        }
+    }
+}
+
+/// Callback telling whether a lookup should be cancelled, for example
+/// because a deadline has passed.
+///
+/// The callback is kept in an [`Arc`], so that options stay cheap to clone
+/// and can be shared between threads along with the transducer.  Hence it
+/// is only available on targets with atomic pointers.
+///
+/// ```
+/// use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+/// use hfstol::{Error, LookupOptions, Transducer, compile_att};
+/// use hfstol::transducers::CancelHook;
+///
+/// let t = compile_att("0\t1\ta\tb\n1\n").unwrap();
+/// let cancelled = Arc::new(AtomicBool::new(true));
+/// let flag = Arc::clone(&cancelled);
+/// let options = LookupOptions {
+///     cancel: Some(CancelHook::new(move || flag.load(Ordering::Relaxed))),
+///     ..LookupOptions::default()
+/// };
+/// assert!(matches!(t.lookup_with("a", &options), Err(Error::Cancelled)));
+/// ```
+#[cfg(target_has_atomic = "ptr")]
+#[derive(Clone)]
+pub struct CancelHook(Arc<dyn Fn() -> bool + Send + Sync>);
+
+#[cfg(target_has_atomic = "ptr")]
+impl CancelHook {
+    pub fn new<F: Fn() -> bool + Send + Sync + 'static>(hook: F) -> Self {
+        CancelHook(Arc::new(hook))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        (self.0)()
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl fmt::Debug for CancelHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CancelHook")
+    }
+}
@@ -0,0 +1,148 @@
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use super::{
+    Transducer,
+    Error,
+    Header, Alphabet,
+    LookupOptions, Trie,
+    TransitionIndex,
+    TransitionTableIndex,
+    Symbol, Weight,
+    transitions::{WeightedTransition, UnweightedTransition, TargetEntry, TransitionTables},
+    lookup::Lookup,
+};
+
+/// Transducer with its transition tables read into memory.
+///
+/// `T` is the type of the target table entries, see [`WeightedTransducer`]
+/// and [`UnweightedTransducer`].
+pub struct TableTransducer<T> {
+    header: Header,
+    alphabet: Alphabet,
+    input_letters: Trie<Symbol>,
+    index: Vec<TransitionIndex>,
+    transitions: Vec<T>,
+    options: LookupOptions,
+}
+
+pub type WeightedTransducer = TableTransducer<WeightedTransition>;
+
+/// Transducer without weights.  All analyses are reported with weight 0.0.
+pub type UnweightedTransducer = TableTransducer<UnweightedTransition>;
+
+impl<T: TargetEntry> TableTransducer<T> {
+    pub fn new(
+        header: Header,
+        alphabet: Alphabet,
+        index: Vec<TransitionIndex>,
+        transitions: Vec<T>,
+    ) -> Self {
+        let input_letters = alphabet.to_trie(header.number_of_input_symbols());
+        TableTransducer {
+            header,
+            alphabet,
+            input_letters,
+            index,
+            transitions,
+            options: LookupOptions::default(),
+        }
+    }
+}
+
+impl<T: TargetEntry> TransitionTables for TableTransducer<T> {
+    fn weighted(&self) -> bool {
+        T::WEIGHTED
+    }
+
+    fn index_table_size(&self) -> TransitionTableIndex {
+        self.index.len() as TransitionTableIndex
+    }
+
+    fn target_table_size(&self) -> TransitionTableIndex {
+        self.transitions.len() as TransitionTableIndex
+    }
+
+    fn transition_index(&self, index: TransitionTableIndex) -> Option<TransitionIndex> {
+        self.index.get(index as usize).copied()
+    }
+
+    fn transition(&self, index: TransitionTableIndex) -> Option<WeightedTransition> {
+        self.transitions.get(index as usize).map(|&tr| tr.into())
+    }
+}
+
+impl<T: TargetEntry> Transducer for TableTransducer<T> {
+    fn lookup_encoded_with(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+    ) -> Result<Vec<(Vec<Symbol>, Weight)>, Error> {
+        Lookup::new(self, &self.alphabet, options).with_header(&self.header).run(input)
+    }
+
+    fn lookup_encoded_each(
+        &self,
+        input: &[Symbol],
+        options: &LookupOptions,
+        f: &mut dyn FnMut(Vec<Symbol>, Weight) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        Lookup::new(self, &self.alphabet, options).with_header(&self.header).run_each(input, f)
+    }
+
+    fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    fn header(&self) -> Option<&Header> {
+        Some(&self.header)
+    }
+
+    fn tables(&self) -> Option<&dyn TransitionTables> {
+        Some(self)
+    }
+
+    fn input_letters(&self) -> &Trie<u16> {
+        &self.input_letters
+    }
+
+    fn options(&self) -> &LookupOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LookupOptions {
+        &mut self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, string::String};
+    use crate::transducers::{read_transducer, Header, Alphabet, TransitionIndex};
+    use crate::transducers::{NO_SYMBOL_NUMBER, NO_TABLE_INDEX, TRANSITION_TARGET_TABLE_START};
+    use super::*;
+
+    #[test]
+    fn unweighted_tables() {
+        // `a:b` with a final state marked by target 1 in the index table
+        let symbols = ["@_EPSILON_SYMBOL_@", "a", "b"];
+        let alphabet = Alphabet::new(symbols.iter().map(|&s| String::from(s)).collect());
+        let index = vec![
+            TransitionIndex::new(NO_SYMBOL_NUMBER, NO_TABLE_INDEX),
+            TransitionIndex::new(NO_SYMBOL_NUMBER, NO_TABLE_INDEX),
+            TransitionIndex::new(1, TRANSITION_TARGET_TABLE_START),
+            TransitionIndex::new(NO_SYMBOL_NUMBER, 1),
+        ];
+        let transitions = vec![UnweightedTransition::new(1, 2, 3)];
+        let mut bytes = vec![];
+        Header::new(2, 3, 4, 1, 2, 1, false).write(&mut bytes).unwrap();
+        alphabet.write(&mut bytes);
+        index.iter().for_each(|tr| tr.write(&mut bytes));
+        transitions.iter().for_each(|tr| tr.write(&mut bytes));
+
+        let t = read_transducer(&bytes).unwrap();
+        assert!(!t.tables().unwrap().weighted());
+        assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), 0.0)]);
+        let t = UnweightedTransducer::new(Header::new(2, 3, 4, 1, 2, 1, false), alphabet, index, transitions);
+        assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), 0.0)]);
+    }
+}
@@ -1,21 +1,38 @@
+use alloc::vec::Vec;
 use nom::{
     IResult,
     number::complete::{le_u16, le_u32, le_f32},
 };
 
 use super::{
-    Symbol, TransitionTableIndex, Weight,
-    NO_SYMBOL_NUMBER, NO_TABLE_INDEX
+    Alphabet, Symbol, EPSILON, TransitionTableIndex, Weight,
+    NO_SYMBOL_NUMBER, NO_TABLE_INDEX, TRANSITION_TARGET_TABLE_START,
 };
 use crate::parser_utils::parse_to_struct;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct TransitionIndex {
     symbol: Symbol,
     target: TransitionTableIndex,
 }
 
 impl TransitionIndex {
+    /// Size of an entry in the binary format.
+    pub const SIZE: usize = 6;
+
+    pub fn new(symbol: Symbol, target: TransitionTableIndex) -> Self {
+        TransitionIndex {
+            symbol,
+            target,
+        }
+    }
+
+    /// Entry marking a state as final with the given weight.  Unweighted
+    /// transducers mark final states with the target `1` instead.
+    pub fn new_final(weight: Weight) -> Self {
+        Self::new(NO_SYMBOL_NUMBER, weight.to_bits())
+    }
+
     pub fn parse(input: &[u8]) -> IResult<&[u8], TransitionIndex> {
         parse_to_struct!(input, TransitionIndex {
             symbol: le_u16,
@@ -23,12 +40,19 @@ impl TransitionIndex {
         })
     }
 
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.symbol.to_le_bytes());
+        out.extend_from_slice(&self.target.to_le_bytes());
+    }
+
     pub fn is_final(&self) -> bool {
         self.symbol == NO_SYMBOL_NUMBER && self.target != NO_TABLE_INDEX
     }
 
+    /// Final weight of the entry.  Weighted transducers store it as the bits
+    /// of a float in place of the target.
     pub fn weight(&self) -> Weight {
-        self.target as Weight
+        Weight::from_bits(self.target)
     }
 
     pub fn target(&self) -> TransitionTableIndex {
@@ -39,7 +63,7 @@ impl TransitionIndex {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct WeightedTransition {
     input: Symbol,
     output: Symbol,
@@ -48,6 +72,28 @@ pub struct WeightedTransition {
 }
 
 impl WeightedTransition {
+    /// Size of an entry in the binary format.
+    pub const SIZE: usize = 12;
+
+    pub fn new(
+        input: Symbol,
+        output: Symbol,
+        target: TransitionTableIndex,
+        weight: Weight,
+    ) -> Self {
+        WeightedTransition {
+            input,
+            output,
+            target,
+            weight,
+        }
+    }
+
+    /// Entry marking a state as final with the given weight.
+    pub fn new_final(weight: Weight) -> Self {
+        Self::new(NO_SYMBOL_NUMBER, NO_SYMBOL_NUMBER, 1, weight)
+    }
+
     pub fn parse(input_: &[u8]) -> IResult<&[u8], WeightedTransition> {
         parse_to_struct!(input_, WeightedTransition {
             input: le_u16,
@@ -57,6 +103,13 @@ impl WeightedTransition {
         })
     }
 
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.input.to_le_bytes());
+        out.extend_from_slice(&self.output.to_le_bytes());
+        out.extend_from_slice(&self.target.to_le_bytes());
+        out.extend_from_slice(&self.weight.to_le_bytes());
+    }
+
     pub fn is_final(&self) -> bool {
         self.input == NO_SYMBOL_NUMBER
             && self.output == NO_SYMBOL_NUMBER
@@ -87,3 +140,207 @@ impl Default for WeightedTransition {
         }
     }
 }
+
+/// Transition of an unweighted transducer.  Same as [`WeightedTransition`],
+/// but without the weight field, so it takes 8 bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct UnweightedTransition {
+    input: Symbol,
+    output: Symbol,
+    target: TransitionTableIndex,
+}
+
+impl UnweightedTransition {
+    /// Size of an entry in the binary format.
+    pub const SIZE: usize = 8;
+
+    pub fn new(input: Symbol, output: Symbol, target: TransitionTableIndex) -> Self {
+        UnweightedTransition {
+            input,
+            output,
+            target,
+        }
+    }
+
+    pub fn parse(input_: &[u8]) -> IResult<&[u8], UnweightedTransition> {
+        parse_to_struct!(input_, UnweightedTransition {
+            input: le_u16,
+            output: le_u16,
+            target: le_u32,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.input.to_le_bytes());
+        out.extend_from_slice(&self.output.to_le_bytes());
+        out.extend_from_slice(&self.target.to_le_bytes());
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.input == NO_SYMBOL_NUMBER
+            && self.output == NO_SYMBOL_NUMBER
+            && self.target == 1
+    }
+
+    pub fn input(&self) -> Symbol {
+        self.input
+    }
+    pub fn output(&self) -> Symbol {
+        self.output
+    }
+    pub fn target(&self) -> TransitionTableIndex {
+        self.target
+    }
+}
+
+impl Default for UnweightedTransition {
+    fn default() -> Self {
+        Self {
+            input: NO_SYMBOL_NUMBER,
+            output: NO_SYMBOL_NUMBER,
+            target: NO_TABLE_INDEX,
+        }
+    }
+}
+
+impl From<WeightedTransition> for UnweightedTransition {
+    fn from(tr: WeightedTransition) -> Self {
+        Self {
+            input: tr.input,
+            output: tr.output,
+            target: tr.target,
+        }
+    }
+}
+
+impl From<UnweightedTransition> for WeightedTransition {
+    fn from(tr: UnweightedTransition) -> Self {
+        Self {
+            input: tr.input,
+            output: tr.output,
+            target: tr.target,
+            weight: 0.0,
+        }
+    }
+}
+
+/// Entry type of a transition target table: [`WeightedTransition`] or
+/// [`UnweightedTransition`].
+pub trait TargetEntry: Copy + Into<WeightedTransition> {
+    /// Whether entries carry weights.
+    const WEIGHTED: bool;
+    /// Size of an entry in the binary format.
+    const SIZE: usize;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self>;
+}
+
+impl TargetEntry for WeightedTransition {
+    const WEIGHTED: bool = true;
+    const SIZE: usize = WeightedTransition::SIZE;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        WeightedTransition::parse(input)
+    }
+}
+
+impl TargetEntry for UnweightedTransition {
+    const WEIGHTED: bool = false;
+    const SIZE: usize = UnweightedTransition::SIZE;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        UnweightedTransition::parse(input)
+    }
+}
+
+/// Read access to the transition index and transition target tables of a
+/// transducer.
+pub trait TransitionTables {
+    /// Whether final weights are stored in the tables.
+    fn weighted(&self) -> bool;
+
+    /// Number of entries in the transition index table.
+    fn index_table_size(&self) -> TransitionTableIndex;
+
+    /// Number of entries in the transition target table.
+    fn target_table_size(&self) -> TransitionTableIndex;
+
+    fn transition_index(&self, index: TransitionTableIndex) -> Option<TransitionIndex>;
+
+    /// Returns an entry of the target table.  Entries of unweighted tables
+    /// are reported with weight 0.0.
+    fn transition(&self, index: TransitionTableIndex) -> Option<WeightedTransition>;
+
+    /// Final weight of a final transition index entry.
+    fn index_weight(&self, tr: &TransitionIndex) -> Weight {
+        if self.weighted() {
+            tr.weight()
+        } else {
+            0.0
+        }
+    }
+
+    /// Final weight of a state, or [`None`] if the state is not final.
+    fn final_weight(&self, state: TransitionTableIndex) -> Option<Weight> {
+        if state >= TRANSITION_TARGET_TABLE_START {
+            self.transition(state - TRANSITION_TARGET_TABLE_START)
+                .filter(|tr| tr.is_final())
+                .map(|tr| tr.weight())
+        } else {
+            self.transition_index(state)
+                .filter(|tr| tr.is_final())
+                .map(|tr| self.index_weight(&tr))
+        }
+    }
+
+    /// Position in the target table where transitions of `state` on `symbol`
+    /// start, or [`None`] if the state has none.
+    ///
+    /// Transitions on one input symbol are contiguous, so callers should read
+    /// entries from the position while their input matches.  Epsilon
+    /// transitions are stored together with flag diacritics.
+    fn find_transitions(
+        &self,
+        state: TransitionTableIndex,
+        symbol: Symbol,
+    ) -> Option<TransitionTableIndex> {
+        if state >= TRANSITION_TARGET_TABLE_START {
+            Some(state - TRANSITION_TARGET_TABLE_START + 1)
+        } else {
+            self.transition_index(state + symbol as TransitionTableIndex + 1)
+                .filter(|tr| tr.symbol() == symbol)
+                .map(|tr| tr.target() - TRANSITION_TARGET_TABLE_START)
+        }
+    }
+}
+
+/// Iterates over transitions of `state` on `symbol`.  Transitions on flag
+/// diacritics are included with epsilon ones.
+pub(crate) fn matching_transitions<'a, T: TransitionTables + ?Sized>(
+    tables: &'a T,
+    alphabet: &'a Alphabet,
+    state: TransitionTableIndex,
+    symbol: Symbol,
+) -> impl Iterator<Item = WeightedTransition> + 'a {
+    tables.find_transitions(state, symbol)
+        .into_iter()
+        .flat_map(|start| start..)
+        .map_while(move |i| tables.transition(i).filter(|tr| {
+            tr.input() == symbol || (symbol == EPSILON && alphabet.is_flag(tr.input()))
+        }))
+}
+
+/// Iterates over transitions of `state` which read an input symbol, including
+/// identity and unknown transitions for symbols out of the alphabet.
+pub(crate) fn reading_transitions<'a, T: TransitionTables + ?Sized>(
+    tables: &'a T,
+    alphabet: &'a Alphabet,
+    state: TransitionTableIndex,
+    symbol: Symbol,
+) -> impl Iterator<Item = WeightedTransition> + 'a {
+    alphabet.readers(symbol)
+        .into_iter()
+        .flatten()
+        .flat_map(move |reader| matching_transitions(tables, alphabet, state, reader))
+        .map(move |tr| alphabet.resolve(tr, symbol))
+}
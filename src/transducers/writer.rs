@@ -0,0 +1,129 @@
+use alloc::vec::Vec;
+use super::{
+    Transducer,
+    Error,
+    Hfst3Header,
+    TransitionIndex,
+    UnweightedTransition,
+    NO_SYMBOL_NUMBER,
+};
+
+/// Options for [`write_transducer()`].
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Whether to write a weighted (`HFST_OLW`) or an unweighted (`HFST_OL`)
+    /// transducer.  Weights are dropped when writing an unweighted one.
+    pub weighted: bool,
+    /// Whether to precede the transducer with an HFST3 header, as HFST tools
+    /// do.  Metadata of the original HFST3 header is kept.
+    pub hfst3_header: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            weighted: true,
+            hfst3_header: true,
+        }
+    }
+}
+
+/// Serializes a transducer to the optimized-lookup binary format.
+///
+/// Returns [`Error::UnsupportedTransducerError`] if the transducer is not
+/// backed by transition tables, and [`Error::HeaderWritingError`] if its HFST3
+/// metadata does not fit in a header.
+///
+/// ```no_run
+/// use hfstol::transducers::WriteOptions;
+///
+/// let content = std::fs::read("./analyser-gt-desc.hfstol").unwrap();
+/// let t = hfstol::read_transducer(&content).unwrap();
+/// let unweighted = hfstol::write_transducer(&*t, &WriteOptions {
+///     weighted: false,
+///     ..WriteOptions::default()
+/// }).unwrap();
+/// std::fs::write("./analyser-gt-desc.unweighted.hfstol", unweighted).unwrap();
+/// ```
+pub fn write_transducer<T: Transducer + ?Sized>(
+    transducer: &T,
+    options: &WriteOptions,
+) -> Result<Vec<u8>, Error> {
+    let (header, tables) = transducer.header().zip(transducer.tables())
+        .ok_or(Error::UnsupportedTransducerError)?;
+    let weighted = options.weighted;
+
+    let mut header = header.clone();
+    header.set_weighted(weighted);
+    header.set_hfst3(options.hfst3_header.then(|| {
+        let mut hfst3 = header.hfst3().cloned().unwrap_or_else(|| {
+            let mut hfst3 = Hfst3Header::new();
+            hfst3.insert("version", "3.3");
+            hfst3.insert("name", "");
+            hfst3
+        });
+        hfst3.insert("type", if weighted { "HFST_OLW" } else { "HFST_OL" });
+        hfst3
+    }));
+
+    let mut out = Vec::new();
+    header.write(&mut out)?;
+    transducer.alphabet().write(&mut out);
+
+    for i in 0..tables.index_table_size() {
+        let tr = tables.transition_index(i).ok_or(Error::TableParsingError)?;
+        let tr = match (tr.is_final(), weighted) {
+            (true, true) => TransitionIndex::new_final(tables.index_weight(&tr)),
+            (true, false) => TransitionIndex::new(NO_SYMBOL_NUMBER, 1),
+            (false, _) => tr,
+        };
+        tr.write(&mut out);
+    }
+
+    for i in 0..tables.target_table_size() {
+        let tr = tables.transition(i).ok_or(Error::TableParsingError)?;
+        if weighted {
+            tr.write(&mut out);
+        } else {
+            UnweightedTransition::from(tr).write(&mut out);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, string::String};
+    use super::*;
+    use crate::transducers::{read_transducer, read_transducers, tests::a_to_b};
+
+    #[test]
+    fn round_trip() {
+        let t = a_to_b();
+        let weighted = write_transducer(&t, &WriteOptions::default()).unwrap();
+        let t = read_transducer(&weighted).unwrap();
+        assert_eq!(t.hfst3_header().unwrap().transducer_type(), Some("HFST_OLW"));
+        assert_eq!(t.lookup("a").unwrap(), vec![(String::from("b"), 1.5)]);
+        assert_eq!(write_transducer(&*t, &WriteOptions::default()).unwrap(), weighted);
+    }
+
+    #[test]
+    fn unweighted_stream() {
+        let t = a_to_b();
+        let mut stream = write_transducer(&t, &WriteOptions {
+            weighted: false,
+            hfst3_header: true,
+        }).unwrap();
+        let size = stream.len();
+        stream.extend(write_transducer(&t, &WriteOptions::default()).unwrap());
+
+        let entries = read_transducers(&stream).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].size, size);
+        assert_eq!(entries[1].offset, size);
+        assert_eq!(entries[0].hfst3_header().unwrap().transducer_type(), Some("HFST_OL"));
+        assert_eq!(entries[0].transducer.lookup("a").unwrap(), vec![(String::from("b"), 0.0)]);
+        assert_eq!(entries[1].transducer.lookup("a").unwrap(), vec![(String::from("b"), 1.5)]);
+    }
+}
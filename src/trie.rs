@@ -1,14 +1,15 @@
 use alloc::{
-    boxed::Box,
+    string::String,
+    vec::Vec,
+    vec,
     str,
 };
-use array_init::array_init;
-
-const SIZE: usize = (u8::MAX as usize) + 1;
 
 /// Trie data structure for storing values associated with prefix strings.
 ///
-/// Nodes of the trie represent one byte of a UTF-8-encoded string.
+/// Nodes of the trie represent one byte of a UTF-8-encoded string.  They are
+/// kept in one vector, and every node lists its children sorted by byte, so a
+/// node costs a few bytes per child instead of a slot for every byte value.
 ///
 /// Search is done on the longest prefix.  That is, given a string,
 /// [`Trie::get()`] would return the value stored for the longest part that is a
@@ -16,35 +17,65 @@ const SIZE: usize = (u8::MAX as usize) + 1;
 ///
 /// ```
 /// use hfstol::trie::Trie;
-/// 
+///
 /// let mut trie = Trie::new(None);
 /// trie.insert("he", -2);
 /// trie.insert("hello", 5);
 /// assert_eq!(trie.get("hello world"), (Some(&5), " world"));
+/// assert_eq!(trie.len(), 2);
+/// assert_eq!(trie.iter_prefix("hel").collect::<Vec<_>>(), [("hello".to_string(), &5)]);
 /// ```
 pub struct Trie<V> {
+    /// The root is the first node.
+    nodes: Vec<Node<V>>,
+    len: usize,
+}
+
+struct Node<V> {
     value: Option<V>,
-    descendants: [Option<Box<Trie<V>>>; SIZE],
+    /// Bytes leading to the children and their indices, sorted by byte.
+    children: Vec<(u8, u32)>,
+}
+
+impl<V> Node<V> {
+    fn new(value: Option<V>) -> Self {
+        Node {
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    fn child(&self, byte: u8) -> Result<u32, usize> {
+        self.children.binary_search_by_key(&byte, |&(b, _)| b)
+            .map(|k| self.children[k].1)
+    }
 }
 
 impl<V> Trie<V> {
+    /// Creates a trie with a value for the empty key, which [`Trie::get()`]
+    /// falls back to.
     pub fn new(value: Option<V>) -> Trie<V> {
         Trie {
-            value,
-            descendants: array_init(|_| None),
+            len: value.is_some() as usize,
+            nodes: vec![Node::new(value)],
         }
     }
 
     pub fn insert_by_bytes_key(&mut self, key: &[u8], value: V) {
-        if key.is_empty() {
-            self.value = Some(value);
+        let mut node = 0;
+        for &byte in key {
+            node = match self.nodes[node].child(byte) {
+                Ok(child) => child as usize,
+                Err(k) => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new(None));
+                    self.nodes[node].children.insert(k, (byte, child as u32));
+                    child
+                },
+            };
         }
-        else if let Some(ref mut sub) = self.descendants[key[0] as usize] {
-            sub.insert_by_bytes_key(&key[1..], value);
-        } else {
-            let mut sub = Box::new(Trie::new(None));
-            sub.insert_by_bytes_key(&key[1..], value);
-            self.descendants[key[0] as usize] = Some(sub);
+        if self.nodes[node].value.replace(value).is_none() {
+            self.len += 1;
         }
     }
 
@@ -53,25 +84,41 @@ impl<V> Trie<V> {
         self.insert_by_bytes_key(key.as_bytes(), value)
     }
 
+    /// Frees spare capacity left by insertions, once the trie is built.
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+        for node in &mut self.nodes {
+            node.children.shrink_to_fit();
+        }
+    }
+
+    /// Returns the number of keys with a value.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// Returns `true` if there exists a key starting with specified `char`.
     pub fn has_key_starting_with(&self, char: u8) -> bool {
-        self.descendants[char as usize].is_some()
+        self.nodes[0].child(char).is_ok()
     }
 
     pub fn get_by_bytes_key<'a, 'b>(&'a self, key: &'b [u8]) -> (Option<&'a V>, &'b [u8]) {
-        if key.is_empty() {
-            (self.value.as_ref(), key)
-        }
-        else if let Some(ref sub) = self.descendants[key[0] as usize] {
-            let (found, rest) = sub.get_by_bytes_key(&key[1..]);
-            if found.is_some() {
-                (found, rest)
-            } else {
-                (self.value.as_ref(), key)
+        let mut node = &self.nodes[0];
+        let mut found = (node.value.as_ref(), key);
+        for (i, &byte) in key.iter().enumerate() {
+            match node.child(byte) {
+                Ok(child) => node = &self.nodes[child as usize],
+                Err(_) => break,
+            }
+            if let Some(value) = &node.value {
+                found = (Some(value), &key[i + 1..]);
             }
-        } else {
-            (self.value.as_ref(), key)
         }
+        found
     }
 
     /// Returns the value associated with the longest prefix of `key` and the
@@ -83,12 +130,74 @@ impl<V> Trie<V> {
         let (found, rest) = self.get_by_bytes_key(key.as_bytes());
         (found, unsafe { str::from_utf8_unchecked(rest) })
     }
+
+    /// Iterates over all the keys and their values, sorted by key bytes.
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.iter_prefix("")
+    }
+
+    /// Iterates over the keys starting with `prefix` and their values, sorted
+    /// by key bytes.
+    pub fn iter_prefix(&self, prefix: &str) -> Iter<'_, V> {
+        let mut node = Some(0);
+        for &byte in prefix.as_bytes() {
+            node = node.and_then(|node| self.nodes[node].child(byte).ok())
+                .map(|child| child as usize);
+        }
+        Iter {
+            trie: self,
+            stack: node.map(|node| (node, prefix.len(), None)).into_iter().collect(),
+            key: prefix.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl<'a, V> IntoIterator for &'a Trie<V> {
+    type Item = (String, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+/// Iterator over keys and values of a [`Trie`].
+///
+/// Keys inserted as bytes which are not valid UTF-8 are converted lossily.
+pub struct Iter<'a, V> {
+    trie: &'a Trie<V>,
+    /// Nodes left to visit, with the length of the key of their parent and
+    /// the byte leading to them.  The node of the prefix has no byte.
+    stack: Vec<(usize, usize, Option<u8>)>,
+    /// Key of the last visited node.
+    key: Vec<u8>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (String, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, depth, byte)) = self.stack.pop() {
+            self.key.truncate(depth);
+            self.key.extend(byte);
+            let node = &self.trie.nodes[node];
+            let depth = self.key.len();
+            self.stack.extend(
+                node.children.iter().rev().map(|&(byte, child)| (child as usize, depth, Some(byte)))
+            );
+            if let Some(value) = &node.value {
+                return Some((String::from_utf8_lossy(&self.key).into_owned(), value));
+            }
+        }
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Trie;
-    
+    use alloc::{string::ToString, vec::Vec};
+
     #[test]
     fn prefix_search() {
         let mut trie = Trie::new(None);
@@ -97,6 +206,25 @@ mod tests {
         assert_eq!(trie.get("orange juice"), (Some(&20), " juice"));
     }
 
+    #[test]
+    fn iteration() {
+        let mut trie = Trie::new(None);
+        for (k, key) in ["+N", "a", "+Pl", "ab", "+N"].iter().enumerate() {
+            trie.insert(key, k);
+        }
+        assert_eq!(trie.len(), 4);
+        let keys: Vec<_> = trie.iter().map(|(key, &k)| (key, k)).collect();
+        assert_eq!(keys, [
+            ("+N".to_string(), 4),
+            ("+Pl".to_string(), 2),
+            ("a".to_string(), 1),
+            ("ab".to_string(), 3),
+        ]);
+        let tags: Vec<_> = trie.iter_prefix("+").map(|(key, _)| key).collect();
+        assert_eq!(tags, ["+N", "+Pl"]);
+        assert_eq!(trie.iter_prefix("b").count(), 0);
+    }
+
     #[test]
     fn fallback() {
         let trie = Trie::new(Some(10));